@@ -0,0 +1,41 @@
+#![no_main]
+
+use chainsweep::field::{Bugs, GameData, BUG, FLAGGED, UNOPENED};
+use libfuzzer_sys::fuzz_target;
+
+// Boards from arbitrary bytes: the width, the height and then the value of every field, which don't
+// have to be valid values. GameData::new needs a value for every field.
+fuzz_target!(|bytes: &[u8]| {
+    let [width, height, values @ ..] = bytes else {
+        return;
+    };
+    let size = *width as usize * *height as usize;
+    if values.len() < size {
+        return;
+    }
+    let values = &values[..size];
+    let board = GameData::new(*width, *height, values.to_vec());
+    assert_eq!(board.data(), values);
+    assert_eq!(board.num_open as usize, values.iter().filter(|&&value| value < BUG).count());
+
+    let valid = values.iter().all(|&value| value <= FLAGGED);
+    if valid && size > 0 {
+        assert_eq!(board.to_string().parse::<GameData>().unwrap().data(), values);
+    }
+
+    // Solving a board can take exponential time if it's made to be hard, so only solve small ones
+    if size > 64 {
+        return;
+    }
+    match board.validate() {
+        Ok(()) => {
+            let filled_in = board.fill_in(0, Bugs::Percentage(20)).expect("a valid board can be filled in");
+            for (before, after) in values.iter().zip(filled_in.data()) {
+                assert!(if *before == UNOPENED || *before == FLAGGED { after != UNOPENED && after != FLAGGED } else { after == *before });
+            }
+            let recounted = GameData::new(*width, *height, filled_in.data());
+            assert!(recounted.validate().is_ok());
+        }
+        Err(_) => assert!(!valid || board.fill_in(0, Bugs::Percentage(20)).is_err()),
+    }
+});
@@ -0,0 +1,263 @@
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+use fastrand::Rng;
+
+// Minimal arbitrary precision unsigned integer, just enough to weigh and sample
+// bug assignments exactly. Floats are not available on chain, and the weights
+// grow as (bug chance)^(number of cells), which doesn't fit in any fixed-size integer.
+//
+// Limbs are little-endian and never have trailing zero limbs, so zero is the empty vec.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct BigUint {
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn normalize(&mut self) {
+        while let Some(&0) = self.limbs.last() {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let a = self.limbs.get(i).copied().unwrap_or(0);
+            let b = other.limbs.get(i).copied().unwrap_or(0);
+            let (sum, c1) = a.overflowing_add(b);
+            let (sum, c2) = sum.overflowing_add(carry);
+            limbs.push(sum);
+            carry = (c1 as u64) + (c2 as u64);
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+        BigUint { limbs }
+    }
+
+    // Panics if other > self
+    pub fn sub(&self, other: &BigUint) -> BigUint {
+        assert!(*self >= *other);
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0u64;
+        for i in 0..self.limbs.len() {
+            let a = self.limbs[i];
+            let b = other.limbs.get(i).copied().unwrap_or(0);
+            let (diff, b1) = a.overflowing_sub(b);
+            let (diff, b2) = diff.overflowing_sub(borrow);
+            limbs.push(diff);
+            borrow = (b1 as u64) + (b2 as u64);
+        }
+        let mut result = BigUint { limbs };
+        result.normalize();
+        result
+    }
+
+    pub fn mul_small(&self, factor: u64) -> BigUint {
+        if factor == 0 {
+            return BigUint::zero();
+        }
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = 0u128;
+        for &limb in self.limbs.iter() {
+            let product = (limb as u128) * (factor as u128) + carry;
+            limbs.push(product as u64);
+            carry = product >> 64;
+        }
+        if carry > 0 {
+            limbs.push(carry as u64);
+        }
+        BigUint { limbs }
+    }
+
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let current = limbs[i + j] as u128 + (a as u128) * (b as u128) + carry;
+                limbs[i + j] = current as u64;
+                carry = current >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+        let mut result = BigUint { limbs };
+        result.normalize();
+        result
+    }
+
+    // Rounded down
+    pub fn div_small(&self, divisor: u64) -> BigUint {
+        assert!(divisor != 0);
+        let mut limbs = vec![0u64; self.limbs.len()];
+        let mut remainder = 0u128;
+        for i in (0..self.limbs.len()).rev() {
+            let current = (remainder << 64) | self.limbs[i] as u128;
+            limbs[i] = (current / divisor as u128) as u64;
+            remainder = current % divisor as u128;
+        }
+        let mut result = BigUint { limbs };
+        result.normalize();
+        result
+    }
+
+    pub fn shl(&self, bits: usize) -> BigUint {
+        if self.is_zero() {
+            return BigUint::zero();
+        }
+        let (limb_shift, bit_shift) = (bits / 64, bits % 64);
+        let mut limbs = vec![0u64; limb_shift];
+        let mut carry = 0u64;
+        for &limb in self.limbs.iter() {
+            if bit_shift == 0 {
+                limbs.push(limb);
+            } else {
+                limbs.push((limb << bit_shift) | carry);
+                carry = limb >> (64 - bit_shift);
+            }
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+        BigUint { limbs }
+    }
+
+    pub fn pow(&self, exponent: usize) -> BigUint {
+        let mut result = BigUint::from(1);
+        let mut square = self.clone();
+        let mut exponent = exponent;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&square);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                square = square.mul(&square);
+            }
+        }
+        result
+    }
+
+    // self / divisor rounded down, the result must fit in a u64
+    pub fn div_floor(&self, divisor: &BigUint) -> u64 {
+        assert!(!divisor.is_zero());
+        assert!(*self < divisor.shl(64));
+        let mut remainder = self.clone();
+        let mut quotient = 0u64;
+        for bit in (0..64).rev() {
+            let shifted = divisor.shl(bit);
+            if shifted <= remainder {
+                remainder = remainder.sub(&shifted);
+                quotient |= 1 << bit;
+            }
+        }
+        quotient
+    }
+
+    // Uniformly random number in 0..self, self must not be zero
+    pub fn random_below(&self, rng: &mut Rng) -> BigUint {
+        assert!(!self.is_zero());
+        let top = *self.limbs.last().unwrap();
+        loop {
+            let mut limbs = Vec::with_capacity(self.limbs.len());
+            for _ in 0..self.limbs.len() - 1 {
+                limbs.push(rng.u64(..));
+            }
+            // Only draw as many bits in the top limb as the bound has, so we reject at most half the time
+            let top_bits = 64 - top.leading_zeros();
+            let top_mask = if top_bits == 64 { u64::MAX } else { (1u64 << top_bits) - 1 };
+            limbs.push(rng.u64(..) & top_mask);
+            let mut candidate = BigUint { limbs };
+            candidate.normalize();
+            if candidate < *self {
+                return candidate;
+            }
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        let mut result = BigUint { limbs: vec![value] };
+        result.normalize();
+        result
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = BigUint::from(u64::MAX);
+        let b = a.add(&BigUint::from(1));
+        assert_eq!(b.limbs, vec![0, 1]);
+        assert_eq!(b.sub(&BigUint::from(1)), a);
+        assert_eq!(b.sub(&b), BigUint::zero());
+        assert_eq!(a.mul_small(u64::MAX).limbs, vec![1, u64::MAX - 1]);
+        assert_eq!(a.mul_small(0), BigUint::zero());
+        assert_eq!(b.mul(&b).limbs, vec![0, 0, 1]);
+        assert_eq!(a.mul(&a), a.mul_small(u64::MAX));
+        assert_eq!(b.mul(&a).mul(&BigUint::zero()), BigUint::zero());
+        assert!(b > a);
+        assert!(BigUint::from(3) < BigUint::from(4));
+        assert_eq!(b.mul_small(6).div_small(3), b.mul_small(2));
+        assert_eq!(b.div_small(3), BigUint::from(u64::MAX / 3));
+        assert_eq!(BigUint::from(3).shl(64).limbs, vec![0, 3]);
+        assert_eq!(BigUint::from(3).shl(63).limbs, vec![1 << 63, 1]);
+        assert_eq!(b.div_floor(&BigUint::from(2)), 1 << 63);
+        assert_eq!(b.mul_small(1000).add(&a).div_floor(&b), 1000);
+        assert_eq!(BigUint::from(999).div_floor(&BigUint::from(1000)), 0);
+        assert_eq!(BigUint::from(2).pow(64), BigUint::from(1).shl(64));
+        assert_eq!(BigUint::from(10).pow(3), BigUint::from(1000));
+        assert_eq!(a.pow(0), BigUint::from(1));
+        assert_eq!(BigUint::zero().pow(5), BigUint::zero());
+        assert_eq!(b.pow(3).limbs, vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_random_below() {
+        let mut rng = Rng::with_seed(42);
+        let bound = BigUint::from(3);
+        let mut seen = [0; 3];
+        for _ in 0..300 {
+            let r = bound.random_below(&mut rng);
+            assert!(r < bound);
+            seen[r.limbs.first().copied().unwrap_or(0) as usize] += 1;
+        }
+        assert!(seen.iter().all(|&count| count > 50));
+
+        let big = BigUint::from(1).add(&BigUint::from(u64::MAX));
+        for _ in 0..100 {
+            assert!(big.random_below(&mut rng) < big);
+        }
+    }
+}
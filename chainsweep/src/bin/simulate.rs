@@ -0,0 +1,295 @@
+#![cfg_attr(all(target_arch = "wasm32", not(feature = "export-abi")), no_main)]
+#![cfg_attr(all(target_arch = "wasm32", not(feature = "export-abi")), no_std)]
+
+// Plays many games with the rules of the contract to see how winnable the game is with a given board
+// size and bug chance, e.g.
+//   cargo run --bin simulate -- --games 1000 --size 5x5,8x8 --bugs 10,15,20 --strategy all
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let options = match simulate::Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, simulate::USAGE);
+            std::process::exit(2);
+        }
+    };
+    println!("{} games per setting, seed {}", options.games, options.seed);
+    for &strategy in options.strategies.iter() {
+        for &(width, height) in options.sizes.iter() {
+            for &bugs in options.bugs.iter() {
+                let stats = simulate::run(&options, strategy, width, height, bugs);
+                println!("{:>8} {:>3}x{:<3} {:>9}: {}", strategy.name(), width, height, simulate::bugs_name(bugs), stats);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod simulate {
+    use std::fmt;
+    use std::time::{Duration, Instant};
+
+    use chainsweep::deduce::deduce;
+    use chainsweep::field::{is_open, Bugs, GameData, Inconsistency, UNOPENED};
+    use chainsweep::hint::suggest_move;
+    use chainsweep::rules::{make_guess, Event, Mode, MoveError};
+    use fastrand::Rng;
+
+    pub const USAGE: &str = "usage: simulate [--games N] [--seed N] [--size WxH,...] [--bugs PERCENTAGE,... | --total N,...] [--strategy random|deduce|greedy|all] [--mode normal|evil|forgiving]";
+
+    // How the simulated player picks the next field to open
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Strategy {
+        // Any unopened field
+        Random,
+        // A provably safe field if there is one, any unopened field otherwise
+        Deduce,
+        // The field with the lowest chance of being a bug
+        Greedy,
+    }
+
+    impl Strategy {
+        pub fn name(&self) -> &'static str {
+            match self {
+                Strategy::Random => "random",
+                Strategy::Deduce => "deduce",
+                Strategy::Greedy => "greedy",
+            }
+        }
+    }
+
+    pub fn bugs_name(bugs: Bugs) -> String {
+        match bugs {
+            Bugs::Percentage(bug_percentage) => format!("{}%", bug_percentage),
+            Bugs::Total(total) => format!("{} bugs", total),
+        }
+    }
+
+    pub struct Options {
+        pub games: u32,
+        pub seed: u64,
+        pub sizes: Vec<(u8, u8)>,
+        pub bugs: Vec<Bugs>,
+        pub strategies: Vec<Strategy>,
+        pub mode: Mode,
+    }
+
+    impl Options {
+        // Defaults are the settings of the contract
+        pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+            let mut options = Options {
+                games: 1000,
+                seed: 0,
+                sizes: vec![(5, 5)],
+                bugs: vec![Bugs::Percentage(20)],
+                strategies: vec![Strategy::Random, Strategy::Deduce, Strategy::Greedy],
+                mode: Mode::Normal,
+            };
+            while let Some(arg) = args.next() {
+                let value = args.next().ok_or(format!("missing value for {}", arg))?;
+                let invalid = || format!("invalid value for {}: {}", arg, value);
+                let numbers = |max: u64| {
+                    value
+                        .split(',')
+                        .map(|number| number.parse::<u64>().ok().filter(|&number| number <= max).ok_or_else(invalid))
+                        .collect::<Result<Vec<u64>, String>>()
+                };
+                match arg.as_str() {
+                    "--games" => options.games = numbers(u32::MAX as u64)?[0] as u32,
+                    "--seed" => options.seed = numbers(u64::MAX)?[0],
+                    "--bugs" => options.bugs = numbers(100)?.into_iter().map(|bugs| Bugs::Percentage(bugs as u8)).collect(),
+                    "--total" => options.bugs = numbers(u16::MAX as u64)?.into_iter().map(|bugs| Bugs::Total(bugs as u16)).collect(),
+                    "--size" => {
+                        options.sizes = value
+                            .split(',')
+                            .map(|size| {
+                                let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+                                match (width.parse::<u8>(), height.parse::<u8>()) {
+                                    (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+                                    _ => Err(invalid()),
+                                }
+                            })
+                            .collect::<Result<Vec<_>, String>>()?
+                    }
+                    "--strategy" => {
+                        options.strategies = match value.as_str() {
+                            "random" => vec![Strategy::Random],
+                            "deduce" => vec![Strategy::Deduce],
+                            "greedy" => vec![Strategy::Greedy],
+                            "all" => vec![Strategy::Random, Strategy::Deduce, Strategy::Greedy],
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    "--mode" => {
+                        options.mode = match value.as_str() {
+                            "normal" => Mode::Normal,
+                            "evil" => Mode::Evil,
+                            "forgiving" => Mode::Forgiving,
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    _ => return Err(format!("unknown option {}", arg)),
+                }
+            }
+            Ok(options)
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Stats {
+        games: u32,
+        won: u32,
+        moves: u64,
+        // Moves where no unopened field was provably safe, not counting the first move
+        forced_guesses: u64,
+        // losses_at[i]: number of games that were lost on move i + 1
+        losses_at: Vec<u32>,
+        // Games that ended because the board couldn't be filled in, which should never happen
+        errors: u32,
+        // Games the player had to abandon because the board got too complex to solve within MAX_SOLVE_STEPS
+        too_complex: u32,
+        // The longest make_guess took, a stand-in for the worst case gas cost of a guess
+        slowest_guess: Duration,
+    }
+
+    impl Stats {
+        fn give_up(&mut self, inconsistency: Inconsistency) {
+            match inconsistency {
+                Inconsistency::TooComplex => self.too_complex += 1,
+                _ => self.errors += 1,
+            }
+        }
+    }
+
+    impl fmt::Display for Stats {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let games = self.games.max(1) as f64;
+            write!(
+                f,
+                "won {:5.1}%, {:5.2} moves, {:5.2} forced guesses per game, lost on move:",
+                100.0 * self.won as f64 / games,
+                self.moves as f64 / games,
+                self.forced_guesses as f64 / games
+            )?;
+            for (index, &losses) in self.losses_at.iter().enumerate() {
+                if losses > 0 {
+                    write!(f, " {}: {}", index + 1, losses)?;
+                }
+            }
+            write!(f, ", slowest guess {:.1} ms", self.slowest_guess.as_secs_f64() * 1000.0)?;
+            if self.too_complex > 0 {
+                write!(f, ", {} too complex", self.too_complex)?;
+            }
+            if self.errors > 0 {
+                write!(f, ", {} errors", self.errors)?;
+            }
+            Ok(())
+        }
+    }
+
+    pub fn run(options: &Options, strategy: Strategy, width: u8, height: u8, bugs: Bugs) -> Stats {
+        let mut stats = Stats::default();
+        let mut rng = Rng::with_seed(options.seed);
+        for _ in 0..options.games {
+            play(&mut stats, &mut rng, options.mode, strategy, width, height, bugs);
+        }
+        stats
+    }
+
+    fn play(stats: &mut Stats, rng: &mut Rng, mode: Mode, strategy: Strategy, width: u8, height: u8, bugs: Bugs) {
+        stats.games += 1;
+        let mut fields = vec![UNOPENED; width as usize * height as usize];
+        let mut moves = 0;
+        loop {
+            let board = GameData::new(width, height, fields.clone());
+            let unopened: Vec<usize> = (0..fields.len()).filter(|&index| !is_open(fields[index])).collect();
+            let safe = match deduce(&board, bugs) {
+                Ok(deduction) => deduction.safe,
+                Err(inconsistency) => return stats.give_up(inconsistency),
+            };
+            if moves > 0 && safe.is_empty() {
+                stats.forced_guesses += 1;
+            }
+            let mut random = || {
+                let index = unopened[rng.usize(0..unopened.len())];
+                ((index % width as usize) as u8, (index / width as usize) as u8)
+            };
+            let (x, y) = match strategy {
+                Strategy::Random => random(),
+                Strategy::Deduce => safe.first().copied().unwrap_or_else(random),
+                Strategy::Greedy => match suggest_move(&board, bugs, true) {
+                    Ok(hints) => (hints[0].x, hints[0].y),
+                    Err(inconsistency) => return stats.give_up(inconsistency),
+                },
+            };
+            moves += 1;
+            let start = Instant::now();
+            let guess = make_guess(&board, x, y, rng.u64(..), bugs, mode);
+            stats.slowest_guess = stats.slowest_guess.max(start.elapsed());
+            let events = match guess {
+                Ok(events) => events,
+                Err(MoveError::Inconsistent(inconsistency)) => return stats.give_up(inconsistency),
+                Err(_) => {
+                    stats.errors += 1;
+                    return;
+                }
+            };
+            for event in events {
+                match event {
+                    Event::FieldOpened { x, y, value, .. } => fields[y as usize * width as usize + x as usize] = value,
+                    Event::GameOver { won } => {
+                        stats.moves += moves as u64;
+                        if won {
+                            stats.won += 1;
+                        } else {
+                            if stats.losses_at.len() < moves {
+                                stats.losses_at.resize(moves, 0);
+                            }
+                            stats.losses_at[moves - 1] += 1;
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn options(args: &[&str]) -> Options {
+            Options::from_args(args.iter().map(|arg| arg.to_string())).unwrap()
+        }
+
+        #[test]
+        fn test_options() {
+            let options = options(&["--size", "5x5,8x4", "--total", "3,4", "--strategy", "greedy"]);
+            assert_eq!(options.sizes, vec![(5, 5), (8, 4)]);
+            assert_eq!(options.bugs, vec![Bugs::Total(3), Bugs::Total(4)]);
+            assert_eq!(options.strategies, vec![Strategy::Greedy]);
+            assert!(Options::from_args(["--bugs".to_string(), "101".to_string()].into_iter()).is_err());
+            assert!(Options::from_args(["--size".to_string(), "0x5".to_string()].into_iter()).is_err());
+        }
+
+        #[test]
+        fn test_run() {
+            let options = options(&["--games", "50", "--seed", "3"]);
+            let random = run(&options, Strategy::Random, 5, 5, Bugs::Percentage(20));
+            let greedy = run(&options, Strategy::Greedy, 5, 5, Bugs::Percentage(20));
+            assert_eq!((random.games, random.errors), (50, 0));
+            assert_eq!(random.won + random.losses_at.iter().sum::<u32>(), 50);
+            // The first move is always safe
+            assert_eq!(random.losses_at.first().copied().unwrap_or(0), 0);
+            assert!(greedy.won > random.won);
+
+            // Without bugs every game is won with the first move, in evil mode the deducer never survives a guess
+            let stats = run(&options, Strategy::Random, 5, 5, Bugs::Percentage(0));
+            assert_eq!((stats.won, stats.moves), (50, 50));
+            let options = Options { mode: Mode::Evil, ..options };
+            let stats = run(&options, Strategy::Deduce, 5, 5, Bugs::Total(5));
+            assert_eq!(stats.won as u64 + stats.forced_guesses, 50);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use alloc::vec::Vec;
+
+use crate::field::{Bugs, GameData, Inconsistency, BUG, FLAGGED, UNOPENED, UNOPENED_BUGFREE};
+
+// What can be known for sure about the unopened fields of a board
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Deduction {
+    // Fields (x, y) that are bug-free in every layout consistent with the board
+    pub safe: Vec<(u8, u8)>,
+    // Fields (x, y) that are a bug in every layout consistent with the board
+    pub bugs: Vec<(u8, u8)>,
+}
+
+// Exactly `bugs` of the undecided fields in `cells` (sorted field indexes) are bugs
+#[derive(PartialEq, Eq)]
+struct Constraint {
+    cells: Vec<usize>,
+    bugs: usize,
+}
+
+// Find all unopened fields that are provably safe or provably a bug. The cheap rules are tried first:
+// single opened fields that need all or none of their neighbours to be bugs, and opened fields whose
+// undecided neighbours are a subset of another's. Whatever is left is settled by counting the
+// solutions of the remaining frontier.
+// With Bugs::Total the total number of bugs is taken into account, the bug chance of Bugs::Percentage
+// doesn't matter for what's possible.
+pub fn deduce(data: &GameData, bugs: Bugs) -> Result<Deduction, Inconsistency> {
+    // Some(true) for a known bug, Some(false) for known bug-free, None if not known yet
+    let mut known: Vec<Option<bool>> = data
+        .data()
+        .iter()
+        .map(|&value| match value {
+            UNOPENED | FLAGGED => None,
+            BUG => Some(true),
+            _ => Some(false),
+        })
+        .collect();
+
+    loop {
+        let constraints = constraints(data, &known);
+        if !apply_single_rules(&constraints, &mut known) && !apply_subset_rules(&constraints, &mut known) {
+            break;
+        }
+    }
+
+    // Any prior where every layout is possible gives the fields that are decided in all layouts
+    let bugs = match bugs {
+        Bugs::Percentage(_) => Bugs::Percentage(50),
+        total => total,
+    };
+    let reduced = GameData::new(data.width(), data.height(), known_data(data, &known));
+    for (index, probability) in reduced.bug_probabilities(bugs)?.into_iter().enumerate() {
+        if let (None, Some(probability)) = (known[index], probability) {
+            if probability.bugs.is_zero() {
+                known[index] = Some(false);
+            } else if probability.bugs == probability.total {
+                known[index] = Some(true);
+            }
+        }
+    }
+
+    let mut result = Deduction::default();
+    for (index, &value) in data.data().iter().enumerate() {
+        let position = ((index % data.width() as usize) as u8, (index / data.width() as usize) as u8);
+        match (value, known[index]) {
+            (UNOPENED | FLAGGED | UNOPENED_BUGFREE, Some(false)) => result.safe.push(position),
+            (UNOPENED | FLAGGED, Some(true)) => result.bugs.push(position),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+// The board data with the fields we know about filled in
+fn known_data(data: &GameData, known: &[Option<bool>]) -> Vec<u8> {
+    data.data()
+        .iter()
+        .zip(known.iter())
+        .map(|(&value, known)| match (value, known) {
+            (UNOPENED | FLAGGED, Some(true)) => BUG,
+            (UNOPENED | FLAGGED, Some(false)) => UNOPENED_BUGFREE,
+            _ => value,
+        })
+        .collect()
+}
+
+// One constraint for every opened field that still has undecided neighbours
+fn constraints(data: &GameData, known: &[Option<bool>]) -> Vec<Constraint> {
+    let mut result: Vec<Constraint> = Vec::new();
+    for (index, &value) in data.data().iter().enumerate() {
+        if value >= BUG {
+            continue;
+        }
+        let (x, y) = ((index % data.width() as usize) as u8, (index / data.width() as usize) as u8);
+        let mut cells = Vec::new();
+        let mut bugs = value as usize;
+        for other in data.surrounding_fields(x, y) {
+            match known[other] {
+                None => cells.push(other),
+                Some(true) => bugs = bugs.saturating_sub(1),
+                Some(false) => {}
+            }
+        }
+        let constraint = Constraint { cells, bugs };
+        if !constraint.cells.is_empty() && !result.contains(&constraint) {
+            result.push(constraint);
+        }
+    }
+    result
+}
+
+fn mark(cells: &[usize], is_bug: bool, known: &mut [Option<bool>]) -> bool {
+    let mut changed = false;
+    for &cell in cells {
+        if known[cell].is_none() {
+            known[cell] = Some(is_bug);
+            changed = true;
+        }
+    }
+    changed
+}
+
+// An opened field with no bugs left around it, or with exactly as many undecided neighbours as bugs left
+fn apply_single_rules(constraints: &[Constraint], known: &mut [Option<bool>]) -> bool {
+    let mut changed = false;
+    for constraint in constraints {
+        if constraint.bugs == 0 {
+            changed |= mark(&constraint.cells, false, known);
+        } else if constraint.bugs == constraint.cells.len() {
+            changed |= mark(&constraint.cells, true, known);
+        }
+    }
+    changed
+}
+
+// If the undecided fields of one opened field are a subset of another's, the fields that only the
+// second one has hold the difference in bugs, which can mean they are all safe or all bugs
+fn apply_subset_rules(constraints: &[Constraint], known: &mut [Option<bool>]) -> bool {
+    let mut changed = false;
+    for small in constraints {
+        for large in constraints {
+            if small.cells.len() >= large.cells.len() || !small.cells.iter().all(|cell| large.cells.contains(cell)) {
+                continue;
+            }
+            let rest: Vec<usize> = large.cells.iter().copied().filter(|cell| !small.cells.contains(cell)).collect();
+            let Some(bugs) = large.bugs.checked_sub(small.bugs) else {
+                continue;
+            };
+            if bugs == 0 {
+                changed |= mark(&rest, false, known);
+            } else if bugs == rest.len() {
+                changed |= mark(&rest, true, known);
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_rules() {
+        let o = UNOPENED;
+        let data = GameData::new(4, 1, vec![0, o, 1, o]);
+        assert_eq!(deduce(&data, Bugs::Percentage(20)).unwrap(), Deduction { safe: vec![(1, 0)], bugs: vec![(3, 0)] });
+    }
+
+    #[test]
+    fn test_subset_rules() {
+        let o = UNOPENED;
+        // The 1-2-1 pattern: bugs under the ones, not under the two
+        let data = GameData::new(3, 2, vec![
+            o, o, o,
+            1, 2, 1,
+        ]);
+        let mut known = vec![None, None, None, Some(false), Some(false), Some(false)];
+        let constraints = constraints(&data, &known);
+        assert!(!apply_single_rules(&constraints, &mut known));
+        assert!(apply_subset_rules(&constraints, &mut known));
+        assert_eq!(known, vec![Some(true), None, Some(true), Some(false), Some(false), Some(false)]);
+
+        assert_eq!(deduce(&data, Bugs::Percentage(20)).unwrap(), Deduction { safe: vec![(1, 0)], bugs: vec![(0, 0), (2, 0)] });
+    }
+
+    #[test]
+    fn test_enumeration() {
+        let o = UNOPENED;
+        // Either the middle field is a bug, or both outer fields are: only the total can tell
+        let data = GameData::new(6, 1, vec![o, 1, o, 1, o, o]);
+        assert_eq!(deduce(&data, Bugs::Percentage(20)).unwrap(), Deduction::default());
+        assert_eq!(deduce(&data, Bugs::Total(1)).unwrap(), Deduction { safe: vec![(0, 0), (4, 0), (5, 0)], bugs: vec![(2, 0)] });
+        assert_eq!(deduce(&data, Bugs::Total(3)).unwrap(), Deduction { safe: vec![(2, 0)], bugs: vec![(0, 0), (4, 0), (5, 0)] });
+
+        // The 1 allows at most one bug next to both numbers, so the 3 needs both bottom fields. Neither
+        // number's fields are a subset of the other's, so the rules alone don't find this.
+        let data = GameData::new(5, 3, vec![
+            o, 1, o, o, o,
+            3, o, o, o, o,
+            o, o, o, o, o,
+        ]);
+        let mut known = data.data().iter().map(|&value| (value != UNOPENED).then_some(false)).collect::<Vec<_>>();
+        let constraints = constraints(&data, &known);
+        assert!(!apply_single_rules(&constraints, &mut known));
+        assert!(!apply_subset_rules(&constraints, &mut known));
+        assert_eq!(deduce(&data, Bugs::Percentage(20)).unwrap(), Deduction { safe: vec![(2, 0), (2, 1)], bugs: vec![(0, 2), (1, 2)] });
+    }
+}
@@ -1,20 +1,76 @@
-use alloc::{vec::Vec, string::String};
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Write};
+use core::str::FromStr;
 use fastrand::Rng;
 
-#[derive(Clone)]
+use crate::bignum::BigUint;
+
+#[derive(Clone, Debug)]
 pub struct Field {
+    // A FLAGGED field has data UNOPENED, the flag is kept separately
     pub data: u8,
-    impacted_by: Vec<u8>,
-    impacts: Vec<u8>,
+    flagged: bool,
+    impacted_by: Vec<u16>,
+    impacts: Vec<u16>,
     pub adjacent_bugs: u8,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct GameData {
     fields: Vec<Field>,
     width: u8,
     height: u8,
-    pub num_open: u8,
+    pub num_open: u16,
+}
+
+// Exact probability, as the fraction bugs / total
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probability {
+    pub bugs: BigUint,
+    pub total: BigUint,
+}
+
+impl Probability {
+    pub fn certain(is_bug: bool) -> Self {
+        Probability {
+            bugs: BigUint::from(is_bug as u64),
+            total: BigUint::from(1),
+        }
+    }
+
+    // Rounded down to an integer number of parts per million
+    pub fn per_million(&self) -> u32 {
+        self.bugs.mul_small(1_000_000).div_floor(&self.total) as u32
+    }
+}
+
+// How the bugs on a board are distributed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bugs {
+    // Every field independently is a bug with this chance, as a percentage
+    Percentage(u8),
+    // The board has exactly this many bugs, including the ones already visible
+    Total(u16),
+}
+
+// Why a board can't be filled in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    // No placement of bugs gives all these opened fields (x, y) the right number of bugs around them
+    Fields(Vec<(u8, u8)>),
+    // The opened fields can be satisfied, but not with the required total number of bugs
+    TotalBugs,
+    // The layouts of the frontier couldn't be counted within MAX_SOLVE_STEPS
+    TooComplex,
+}
+
+// What GameData::validate found wrong with a board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidBoard {
+    // Fields (x, y) with a value that isn't one of the encodings below
+    Values(Vec<(u8, u8)>),
+    // The opened fields can't all be right at the same time
+    Inconsistent(Inconsistency),
 }
 
 // We encode each field in 4 bits, so make sure to use at most 0-15
@@ -26,25 +82,84 @@ pub struct GameData {
 pub const BUG: u8 = 9;
 pub const UNOPENED: u8 = 10;
 pub const UNOPENED_BUGFREE: u8 = 11;
+// An unopened field the player marked as a suspected bug. The flag is only shown, when solving the
+// board it's an unopened field like any other.
+pub const FLAGGED: u8 = 12;
+
+// How many times solving a board may assign a frontier field before it gives up with
+// Inconsistency::TooComplex. The number of layouts of the frontier can grow exponentially with its
+// size, and a guess has to fit in the gas of a transaction.
+pub const MAX_SOLVE_STEPS: u64 = 1_000_000;
 
 pub fn is_open(data: u8) -> bool {
     data < UNOPENED
 }
 
+// Text form of the field values: digits for opened fields, X for bugs, . for unopened fields,
+// - for unopened fields that are known to be bug-free and F for flagged fields. ? marks values that
+// aren't valid.
+pub fn to_char(data: u8) -> char {
+    match data {
+        0..=8 => (b'0' + data) as char,
+        BUG => 'X',
+        UNOPENED => '.',
+        UNOPENED_BUGFREE => '-',
+        FLAGGED => 'F',
+        _ => '?',
+    }
+}
+
+// The reverse of to_char. A space is also read as an unopened field, as that is how the
+// game shows unopened fields to players.
+pub fn from_char(c: char) -> Option<u8> {
+    match c {
+        '0'..='8' => Some(c as u8 - b'0'),
+        'X' => Some(BUG),
+        '.' | ' ' => Some(UNOPENED),
+        '-' => Some(UNOPENED_BUGFREE),
+        'F' => Some(FLAGGED),
+        _ => None,
+    }
+}
+
+// Why a text board couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    // Unknown character at (x, y)
+    Character(u8, u8),
+    // Row y isn't as long as the first row
+    RowLength(u8),
+    // No rows, or more than 255 rows or columns
+    Size,
+}
+
+impl Field {
+    // data, or FLAGGED for a flagged field that is still unopened
+    fn value(&self) -> u8 {
+        if self.flagged && self.data == UNOPENED {
+            FLAGGED
+        } else {
+            self.data
+        }
+    }
+}
+
 impl GameData {
     pub fn new(width: u8, height: u8, data: Vec<u8>) -> Self {
         let mut fields = Vec::new();
         let mut num_open = 0;
         for y in 0..height {
             for x in 0..width {
-                let field = Field { data: data[(y as usize)*(width as usize) + (x as usize)], impacted_by: Vec::new(), impacts: Vec::new(), adjacent_bugs: 0u8 };
+                let value = data[(y as usize)*(width as usize) + (x as usize)];
+                let flagged = value == FLAGGED;
+                let field = Field { data: if flagged { UNOPENED } else { value }, flagged, impacted_by: Vec::new(), impacts: Vec::new(), adjacent_bugs: 0u8 };
                 fields.push(field);
             }
         }
 
         for y in 0..height {
             for x in 0..width {
-                let index = y * width + x;
+                let index = (y as u16) * (width as u16) + (x as u16);
                 let add_impact = fields[index as usize].data < BUG;
                 if add_impact {
                     num_open += 1;
@@ -56,12 +171,12 @@ impl GameData {
                         if i == 0 && j == 0 {
                             continue;
                         }
-                        let x = x as i8 + i;
-                        let y = y as i8 + j;
-                        if x < 0 || x as u8 >= width || y < 0 || y as u8 >= height {
+                        let x = x as i16 + i;
+                        let y = y as i16 + j;
+                        if x < 0 || x >= width as i16 || y < 0 || y >= height as i16 {
                             continue;
                         }
-                        let other_index = (y as u8) * width + (x as u8);
+                        let other_index = (y as u16) * (width as u16) + (x as u16);
                         let field = &mut fields[other_index as usize];
                         let add_impact = add_impact && field.data == UNOPENED;
                         if field.data == BUG {
@@ -96,17 +211,36 @@ impl GameData {
         self.fields[(y as usize)*(self.width as usize) + (x as usize)].data = data;
     }
 
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    // The data of all fields, indexed by y * width + x, as passed to new. Flags stay on fields until
+    // they are opened or filled in.
+    pub fn data(&self) -> Vec<u8> {
+        self.fields.iter().map(Field::value).collect()
+    }
+
+    pub fn is_flagged(&self, x: u8, y: u8) -> bool {
+        self.get(x, y).value() == FLAGGED
+    }
 
-    fn surrounding_fields(&self, x: u8, y: u8) -> Vec<usize> {
+
+    // Indexes (y * width + x) of the fields around (x, y)
+    pub fn surrounding_fields(&self, x: u8, y: u8) -> Vec<usize> {
         let mut result = Vec::new();
         for i in -1..=1 {
             for j in -1..=1 {
                 if i == 0 && j == 0 {
                     continue;
                 }
-                let x = x as i8 + i;
-                let y = y as i8 + j;
-                if x < 0 || x as u8 >= self.width || y < 0 || y as u8 >= self.height {
+                let x = x as i16 + i;
+                let y = y as i16 + j;
+                if x < 0 || x >= self.width as i16 || y < 0 || y >= self.height as i16 {
                     continue;
                 }
                 let index = (y as usize)*(self.width as usize) + (x as usize);
@@ -116,122 +250,839 @@ impl GameData {
         result
     }
 
-    fn try_field(&mut self, handle_fields: &Vec<(u8, u8)>, proposed_bugs: u64, is_bug: bool) -> Result<(), ()> {
-        let (x, y) = handle_fields[0];
-        // Update all fields around and check that they're OK
-        let my_index = y * self.width + x;
-        assert!(self.fields[my_index as usize].data == UNOPENED);
-        let mut all_ok = true;
-        if is_bug {
-            self.fields[my_index as usize].data = BUG;
-        } else {
-            self.fields[my_index as usize].data = UNOPENED_BUGFREE;
-        }
-        self.surrounding_fields(x, y).into_iter().for_each(|index| {
-            let field = &mut self.fields[index];
+    // Mark an unopened field as bug or bug-free and update the counts of the fields around it
+    fn decide(&mut self, index: u16, is_bug: bool) {
+        assert!(self.fields[index as usize].data == UNOPENED);
+        self.fields[index as usize].data = if is_bug { BUG } else { UNOPENED_BUGFREE };
+        let (x, y) = ((index % self.width as u16) as u8, (index / self.width as u16) as u8);
+        self.surrounding_fields(x, y).into_iter().for_each(|other| {
+            let field = &mut self.fields[other];
             if is_bug {
                 field.adjacent_bugs += 1;
             }
-            // remove this index from the impacted_by
-            field.impacted_by.retain(|&x| x != my_index);
-            all_ok = all_ok && (field.data > 8 || (field.data >= field.adjacent_bugs && field.data <= field.adjacent_bugs + (field.impacted_by.len() as u8)));
+            field.impacted_by.retain(|&x| x != index);
         });
+    }
 
-        if all_ok {
-            let mut new_fields = handle_fields[1..].to_vec();
-            // Add not-yet existing ones from impacted fields
-            for index in self.fields[my_index as usize].impacts.iter() {
-                for target in self.fields[*index as usize].impacted_by.iter() {
-                    let (x, y) = (target % self.width, target / self.width);
-                    if !new_fields.contains(&(x, y)) {
-                        new_fields.push((x, y));
+    // Groups of unopened fields next to at least one opened field. Fields in different groups don't
+    // share an opened neighbour, so the bugs in one group don't restrict the other groups and every
+    // group can be solved on its own. Within a group, fields that share an opened neighbour are kept
+    // close together in the order, so the search can reject bad assignments early.
+    fn frontier_components(&self) -> Vec<Vec<u16>> {
+        let mut result = Vec::new();
+        let mut seen = vec![false; self.fields.len()];
+        for start in 0..self.fields.len() {
+            let field = &self.fields[start];
+            if seen[start] || field.data != UNOPENED || field.impacts.is_empty() {
+                continue;
+            }
+            seen[start] = true;
+            let mut component = vec![start as u16];
+            let mut next = 0;
+            while next < component.len() {
+                let index = component[next] as usize;
+                next += 1;
+                for opened in self.fields[index].impacts.iter() {
+                    for &other in self.fields[*opened as usize].impacted_by.iter() {
+                        if !seen[other as usize] {
+                            seen[other as usize] = true;
+                            component.push(other);
+                        }
                     }
                 }
             }
+            result.push(component);
+        }
+        result
+    }
 
-            if self.update_field(&new_fields, proposed_bugs).is_ok() {
-                return Ok(());
+    fn position(&self, index: usize) -> (u8, u8) {
+        ((index % self.width as usize) as u8, (index / self.width as usize) as u8)
+    }
+
+    // Count the consistent bug assignments of every group of frontier fields, and if asked also how many
+    // of them have a bug in each field.
+    fn solve(&self, with_cell_counts: bool) -> Result<Vec<Component>, Inconsistency> {
+        let mut search = FrontierSearch::new(self);
+        search.steps_left = MAX_SOLVE_STEPS;
+        let impossible: Vec<(u8, u8)> = (0..self.fields.len())
+            .filter(|&index| !search.is_ok(index))
+            .map(|index| self.position(index))
+            .collect();
+        if !impossible.is_empty() {
+            return Err(Inconsistency::Fields(impossible));
+        }
+        let mut components = Vec::new();
+        for cells in self.frontier_components() {
+            let mut counts = vec![0u64; cells.len() + 1];
+            let mut cell_counts = if with_cell_counts { vec![vec![0u64; cells.len() + 1]; cells.len()] } else { Vec::new() };
+            search.search(&cells, |is_bug, bugs| {
+                counts[bugs] += 1;
+                if with_cell_counts {
+                    for (pos, &is_bug) in is_bug.iter().enumerate() {
+                        if is_bug {
+                            cell_counts[pos][bugs] += 1;
+                        }
+                    }
+                }
+                false
+            });
+            if search.steps_left == 0 {
+                return Err(Inconsistency::TooComplex);
+            }
+            if counts.iter().all(|&count| count == 0) {
+                // All opened fields around this group together can't be satisfied
+                let mut opened: Vec<usize> = cells
+                    .iter()
+                    .flat_map(|&cell| self.fields[cell as usize].impacts.iter().map(|&index| index as usize))
+                    .collect();
+                opened.sort();
+                opened.dedup();
+                return Err(Inconsistency::Fields(opened.into_iter().map(|index| self.position(index)).collect()));
             }
+            components.push(Component { cells, counts, cell_counts });
         }
+        Ok(components)
+    }
 
-        // Undo all changes
-        self.surrounding_fields(x, y).into_iter().for_each(|index| {
-            let field = &mut self.fields[index];
-            if is_bug {
-                field.adjacent_bugs -= 1;
+    fn num_bugs(&self) -> usize {
+        self.fields.iter().filter(|field| field.data == BUG).count()
+    }
+
+    // Check that the board makes sense: every field holds a valid value, and there is a placement of
+    // bugs in the unopened fields that gives every opened field the number of bugs around it that it shows
+    pub fn validate(&self) -> Result<(), InvalidBoard> {
+        let invalid: Vec<(u8, u8)> = (0..self.fields.len())
+            .filter(|&index| self.fields[index].data > UNOPENED_BUGFREE)
+            .map(|index| self.position(index))
+            .collect();
+        if !invalid.is_empty() {
+            return Err(InvalidBoard::Values(invalid));
+        }
+        self.solve(false).map_err(InvalidBoard::Inconsistent)?;
+        Ok(())
+    }
+
+    // The bug counter for a board with total bugs in it: the bugs that are neither visible nor flagged
+    // by the player. Like in classic minesweeper it trusts the flags, and it stops at 0 when there are
+    // more flags than bugs left.
+    pub fn remaining_bugs(&self, total: u16) -> u16 {
+        let flagged = self.fields.iter().filter(|field| field.value() == FLAGGED).count();
+        total.saturating_sub((self.num_bugs() + flagged) as u16)
+    }
+
+    // Number of ways to place the bugs that are not on the frontier in the unopened fields off the
+    // frontier, for every number of bugs from 0 to frontier on the frontier. If one_bug is set, one
+    // specific field off the frontier is required to be a bug.
+    fn placement_counts(&self, frontier: usize, total: u16, one_bug: bool) -> Vec<BigUint> {
+        let unopened = self.fields.iter().filter(|field| field.data == UNOPENED).count();
+        let extra = one_bug as usize;
+        let interior = (unopened - frontier).checked_sub(extra);
+        let remaining = (total as usize).checked_sub(self.num_bugs() + extra);
+        let (Some(interior), Some(remaining)) = (interior, remaining) else {
+            return vec![BigUint::zero(); frontier + 1];
+        };
+        let binomials = binomials(interior);
+        (0..=frontier)
+            .map(|bugs| match remaining.checked_sub(bugs) {
+                Some(rest) if rest <= interior => binomials[rest].clone(),
+                _ => BigUint::zero(),
+            })
+            .collect()
+    }
+
+    // Decide how many bugs go in every component. With a percentage the components are independent,
+    // with a total number of bugs the components and the fields off the frontier have to share them.
+    fn choose_component_bugs(&self, components: &[Component], bugs: Bugs, r: &mut Rng) -> Result<Vec<usize>, Inconsistency> {
+        Ok(match bugs {
+            Bugs::Percentage(bug_percentage) => components
+                .iter()
+                .map(|component| {
+                    let weights: Vec<BigUint> = percentage_weights(&component.counts, bug_percentage)
+                        .iter()
+                        .zip(component.counts.iter())
+                        .map(|(weight, &count)| weight.mul_small(count))
+                        .collect();
+                    pick(&weights, r).unwrap()
+                })
+                .collect(),
+            Bugs::Total(total) => {
+                // prefix[i][k]: number of solutions for the first i components together with k bugs
+                let prefix = prefix_counts(components);
+                let frontier = prefix.last().unwrap().len() - 1;
+                let weights: Vec<BigUint> = prefix
+                    .last()
+                    .unwrap()
+                    .iter()
+                    .zip(self.placement_counts(frontier, total, false).iter())
+                    .map(|(count, placements)| count.mul(placements))
+                    .collect();
+                let mut num_bugs = pick(&weights, r).ok_or(Inconsistency::TotalBugs)?;
+                let mut result = vec![0; components.len()];
+                for i in (0..components.len()).rev() {
+                    let weights: Vec<BigUint> = components[i]
+                        .counts
+                        .iter()
+                        .enumerate()
+                        .map(|(bugs, &count)| match num_bugs.checked_sub(bugs) {
+                            Some(rest) if rest < prefix[i].len() => prefix[i][rest].mul_small(count),
+                            _ => BigUint::zero(),
+                        })
+                        .collect();
+                    result[i] = pick(&weights, r).unwrap();
+                    num_bugs -= result[i];
+                }
+                result
             }
-            field.impacted_by.push(my_index);
-        });
-        self.fields[my_index as usize].data = UNOPENED;
+        })
+    }
 
-        Err(())
+    // Fill in all unopened fields with either BUG or UNOPENED_BUGFREE so that the already opened
+    // fields are consistent with the number of adjacent bugs.
+    // Every consistent assignment is picked with probability proportional to its chance of occurring
+    // under bugs: either each field independently is a bug with a fixed chance, or all layouts with
+    // the given total number of bugs are equally likely.
+    // Unopened fields without an opened neighbour don't constrain anything, so only the fields on the
+    // frontier are enumerated (counting the solutions per number of bugs), one solution is drawn
+    // from those counts and the rest of the fields are filled in randomly.
+    pub fn fill_in(&self, rand: u64, bugs: Bugs) -> Result<GameData, Inconsistency> {
+        let mut r = Rng::with_seed(rand);
+        let components = self.solve(false)?;
+        self.fill_in_solved(&components, &mut r, bugs, None)
     }
 
-    fn update_field(&mut self, fields: &Vec<(u8, u8)>, proposed_bugs: u64) -> Result<(), ()> {
-        if fields.is_empty() {
-            return Ok(());
+    // fill_in with the frontier already solved. If forced is set, that field gets the given outcome (is
+    // it a bug) and the rest of the board is filled in consistent with it, which needs the cell counts of
+    // solve(true) when the field is on the frontier.
+    fn fill_in_solved(&self, components: &[Component], r: &mut Rng, bugs: Bugs, forced: Option<(u16, bool)>) -> Result<GameData, Inconsistency> {
+        let mut result = self.clone();
+        let mut components = components;
+        let conditioned: Vec<Component>;
+        // (component, position in the component, is it a bug) of a forced field on the frontier
+        let mut forced_cell = None;
+        if let Some((index, is_bug)) = forced {
+            let found = components
+                .iter()
+                .enumerate()
+                .find_map(|(c, component)| component.cells.iter().position(|&cell| cell == index).map(|pos| (c, pos)));
+            match found {
+                Some((c, pos)) => {
+                    let mut changed = components.to_vec();
+                    let component = &mut changed[c];
+                    component.counts = component
+                        .counts
+                        .iter()
+                        .zip(component.cell_counts[pos].iter())
+                        .map(|(&all, &with_bug)| if is_bug { with_bug } else { all - with_bug })
+                        .collect();
+                    if component.counts.iter().all(|&count| count == 0) {
+                        return Err(Inconsistency::Fields(vec![self.position(index as usize)]));
+                    }
+                    conditioned = changed;
+                    components = &conditioned;
+                    forced_cell = Some((c, pos, is_bug));
+                }
+                // Off the frontier the field doesn't change the solutions, only the fields and bugs left
+                None => result.decide(index, is_bug),
+            }
         }
-        let proposed_bug = ((proposed_bugs >> (fields[0].0 * self.height + fields[0].1)) & 1) == 1;
-        if let Err(()) = self.try_field(fields, proposed_bugs, proposed_bug) {
-            self.try_field(fields, proposed_bugs, !proposed_bug)
-        } else {
-            Ok(())
+        let known_bugs = result.num_bugs();
+        let component_bugs = result.choose_component_bugs(components, bugs, r)?;
+
+        let mut search = FrontierSearch::new(self);
+        for (c, (component, &num_bugs)) in components.iter().zip(component_bugs.iter()).enumerate() {
+            let mut skip = r.u64(0..component.counts[num_bugs]);
+            search.search(&component.cells, |is_bug, bugs| {
+                if bugs != num_bugs {
+                    return false;
+                }
+                if let Some((forced_c, pos, forced_bug)) = forced_cell {
+                    if forced_c == c && is_bug[pos] != forced_bug {
+                        return false;
+                    }
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    return false;
+                }
+                for (index, is_bug) in component.cells.iter().zip(is_bug) {
+                    result.decide(*index, *is_bug);
+                }
+                true
+            });
+        }
+
+        let mut interior: Vec<u16> = (0..result.fields.len() as u16)
+            .filter(|&index| result.fields[index as usize].data == UNOPENED)
+            .collect();
+        match bugs {
+            Bugs::Percentage(bug_percentage) => {
+                for index in interior {
+                    let p = r.u8(0..100);
+                    result.decide(index, p < bug_percentage);
+                }
+            }
+            Bugs::Total(total) => {
+                // Shuffle the bugs that are left to the front
+                let rest = total as usize - known_bugs - component_bugs.iter().sum::<usize>();
+                for i in 0..rest {
+                    let j = r.usize(i..interior.len());
+                    interior.swap(i, j);
+                }
+                for (i, index) in interior.into_iter().enumerate() {
+                    result.decide(index, i < rest);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Number of ways to place bugs in the unopened fields that give every opened field the right number
+    // of bugs around it, no matter how many bugs that takes
+    pub fn count_layouts(&self) -> Result<BigUint, Inconsistency> {
+        let components = self.solve(false)?;
+        let frontier: usize = components.iter().map(|component| component.cells.len()).sum();
+        let interior = self.fields.iter().filter(|field| field.data == UNOPENED).count() - frontier;
+        Ok(components
+            .iter()
+            .fold(BigUint::from(1), |total, component| total.mul(&BigUint::from(component.counts.iter().sum::<u64>())))
+            .shl(interior))
+    }
+
+    // Like count_layouts, but taking into account how the bugs are distributed. With Bugs::Total only
+    // layouts with exactly that many bugs count. With Bugs::Percentage every layout with k bugs out of
+    // n unopened fields counts as p^k * (100 - p)^(n - k), so divided by 100^n this is the chance that
+    // randomly placed bugs are consistent with the board.
+    pub fn count_layouts_with(&self, bugs: Bugs) -> Result<BigUint, Inconsistency> {
+        let components = self.solve(false)?;
+        Ok(match bugs {
+            Bugs::Percentage(bug_percentage) => {
+                let bug_weight = BigUint::from(bug_percentage.min(100) as u64);
+                let free_weight = BigUint::from(100 - bug_percentage.min(100) as u64);
+                let frontier: usize = components.iter().map(|component| component.cells.len()).sum();
+                let interior = self.fields.iter().filter(|field| field.data == UNOPENED).count() - frontier;
+                components
+                    .iter()
+                    .map(|component| {
+                        let num_fields = component.cells.len();
+                        component.counts.iter().enumerate().filter(|(_, &count)| count > 0).fold(BigUint::zero(), |total, (bugs, &count)| {
+                            total.add(&bug_weight.pow(bugs).mul(&free_weight.pow(num_fields - bugs)).mul_small(count))
+                        })
+                    })
+                    .fold(BigUint::from(100).pow(interior), |total, weight| total.mul(&weight))
+            }
+            Bugs::Total(total) => {
+                let prefix = prefix_counts(&components);
+                let all = prefix.last().unwrap();
+                all.iter()
+                    .zip(self.placement_counts(all.len() - 1, total, false).iter())
+                    .fold(BigUint::zero(), |total, (count, placements)| total.add(&count.mul(placements)))
+            }
+        })
+    }
+
+    // All ways to fill in the unopened fields with BUG or UNOPENED_BUGFREE that are consistent with the
+    // opened fields, like fill_in but without the randomness. The layouts are found one at a time, so
+    // use take() to only look at the first few on boards that have too many to go through.
+    // Nothing is returned for boards that can't be filled in.
+    pub fn layouts(&self) -> Layouts<'_> {
+        let search = FrontierSearch::new(self);
+        let consistent = (0..self.fields.len()).all(|index| search.is_ok(index));
+        // The frontier first, so bad assignments are found before the fields that can't be wrong
+        let mut cells = self.frontier_components().concat();
+        let mut on_frontier = vec![false; self.fields.len()];
+        cells.iter().for_each(|&index| on_frontier[index as usize] = true);
+        cells.extend(
+            (0..self.fields.len() as u16)
+                .filter(|&index| self.fields[index as usize].data == UNOPENED && !on_frontier[index as usize]),
+        );
+        let mut state = SearchState::new(cells.len());
+        if !consistent {
+            state.next = None;
+        }
+        Layouts {
+            data: self,
+            search,
+            cells,
+            state,
         }
     }
 
-    // Fill in all unopened fields with either BUG or UNOPENED_BUGFREE so that the already opened 
-    // fields are consistent with the number of adjacent bugs
-    // Note: this is generating a mostly randomly sampled solution but not perfectly, especially when 
-    // bug_percentage is close to 0 or 100.
-    // We 'propose' a bug yes/no for every field and then try to 'fix up' the game to be consistent with
-    // the opened fields. This fix-up process is not randomized and so if the proposals are not very random
-    // (e.g. all 1 or all 0), the result will not be very random either.
-    pub fn fill_in(&self, rand: u64, bug_percentage: u8) -> GameData {
+    // Open the field (x, y) with a single random draw against its exact chance of being a bug, and only
+    // then fill in the rest of the board consistent with the outcome. The combined result has the same
+    // distribution as fill_in, but the odds of the guess itself are known and returned for auditing.
+    pub fn reveal(&self, rand: u64, bugs: Bugs, x: u8, y: u8) -> Result<(GameData, Probability), Inconsistency> {
+        let components = self.solve(true)?;
+        let probabilities = self.probabilities(&components, bugs)?;
+        self.reveal_solved(&components, probabilities, rand, bugs, x, y)
+    }
+
+    // reveal with the frontier solved by solve(true), and the probabilities from it
+    fn reveal_solved(
+        &self,
+        components: &[Component],
+        mut probabilities: Vec<Option<Probability>>,
+        rand: u64,
+        bugs: Bugs,
+        x: u8,
+        y: u8,
+    ) -> Result<(GameData, Probability), Inconsistency> {
         let mut r = Rng::with_seed(rand);
-        // bit in bugs is 1 if we will start trying with a bug in that field, 0 if we start with no bug
-        let mut bugs = 0u64;
-        for _ in 0..self.fields.len() {
-            let p = r.u8(0..100);
-            bugs <<= 1;
-            if p < bug_percentage {
-                bugs += 1;
+        let index = (y as usize) * (self.width as usize) + (x as usize);
+        let probability = probabilities.swap_remove(index).unwrap_or_else(|| Probability::certain(self.fields[index].data == BUG));
+        let forced = (self.fields[index].data == UNOPENED).then(|| (index as u16, probability.total.random_below(&mut r) < probability.bugs));
+        Ok((self.fill_in_solved(components, &mut r, bugs, forced)?, probability))
+    }
+
+    // The chance that the field (x, y) is a bug, see bug_probabilities. Opened fields are certain.
+    pub fn bug_probability(&self, bugs: Bugs, x: u8, y: u8) -> Result<Probability, Inconsistency> {
+        let index = (y as usize) * (self.width as usize) + (x as usize);
+        let certain = Probability::certain(self.fields[index].data == BUG);
+        Ok(self.bug_probabilities(bugs)?.swap_remove(index).unwrap_or(certain))
+    }
+
+    // Like reveal, but adversarial about the field (x, y) the player is about to open: if there is any
+    // consistent layout with a bug there, the result is one of those layouts. Only guesses that are
+    // logically forced to be bug-free survive. The returned chance is the one that was applied, which is
+    // certain either way for an unopened field.
+    pub fn fill_in_evil(&self, rand: u64, bugs: Bugs, x: u8, y: u8) -> Result<(GameData, Probability), Inconsistency> {
+        let index = (y as u16) * (self.width as u16) + (x as u16);
+        if self.fields[index as usize].data == UNOPENED {
+            let mut with_bug = self.clone();
+            with_bug.decide(index, true);
+            if let Ok(result) = with_bug.fill_in(rand, bugs) {
+                return Ok((result, Probability::certain(true)));
             }
         }
+        // No layout has a bug there
+        self.reveal(rand, bugs, x, y)
+    }
 
-        let mut result = self.clone();
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let field = &result.fields[(y as usize)*(self.width as usize) + (x as usize)];
-                if field.data == UNOPENED {
-                    let mut process_fields = Vec::new();
-                    process_fields.push((x,y));
-                    if let Err(()) = result.update_field(&process_fields, bugs) {
-                        panic!("Could not fill in field {} {}", x, y);
-                    }
+    // Like reveal, but forgiving towards the field (x, y) the player is about to open: if no unopened
+    // field is provably safe, the player had to guess, and the result is a layout without a bug there.
+    // When there was a safe move, the guess is treated like in reveal. The returned chance is the one
+    // that was applied, so it's 0 for a forgiven guess.
+    // The board is solved once, for finding out if there was a safe move as well as for the reveal.
+    pub fn fill_in_forgiving(&self, rand: u64, bugs: Bugs, x: u8, y: u8) -> Result<(GameData, Probability), Inconsistency> {
+        let index = (y as usize) * (self.width as usize) + (x as usize);
+        let components = self.solve(true)?;
+        let probabilities = self.probabilities(&components, bugs)?;
+        if self.fields[index].data == UNOPENED {
+            // Like deduce: with a bug chance that allows every layout, the fields that are bug-free in all
+            // of them have no chance at all of being a bug
+            let possible = match bugs {
+                Bugs::Percentage(_) => self.probabilities(&components, Bugs::Percentage(50))?,
+                Bugs::Total(_) => probabilities.clone(),
+            };
+            let is_safe = |probability: &Option<Probability>| probability.as_ref().is_some_and(|probability| probability.bugs.is_zero());
+            let certain_bug = possible[index].as_ref().is_some_and(|probability| probability.bugs == probability.total);
+            if !possible.iter().any(is_safe) && !certain_bug {
+                let mut r = Rng::with_seed(rand);
+                let result = self.fill_in_solved(&components, &mut r, bugs, Some((index as u16, false)))?;
+                return Ok((result, Probability::certain(false)));
+            }
+        }
+        self.reveal_solved(&components, probabilities, rand, bugs, x, y)
+    }
+
+    // The exact probability for every unopened field that it holds a bug, given the opened fields and
+    // how the bugs are distributed. This is the chance that fill_in puts a bug there.
+    // The result is indexed by y * width + x, opened fields get None.
+    pub fn bug_probabilities(&self, bugs: Bugs) -> Result<Vec<Option<Probability>>, Inconsistency> {
+        let components = self.solve(true)?;
+        self.probabilities(&components, bugs)
+    }
+
+    // bug_probabilities with the frontier solved by solve(true)
+    fn probabilities(&self, components: &[Component], bugs: Bugs) -> Result<Vec<Option<Probability>>, Inconsistency> {
+        let mut result: Vec<Option<Probability>> = self
+            .fields
+            .iter()
+            .map(|field| match field.data {
+                UNOPENED_BUGFREE => Some(Probability {
+                    bugs: BigUint::zero(),
+                    total: BigUint::from(1),
+                }),
+                _ => None,
+            })
+            .collect();
+        // weights[i][k]: weight of one solution of component i with k bugs, including everything outside the component
+        let (weights, interior) = match bugs {
+            Bugs::Percentage(bug_percentage) => {
+                let weights = components
+                    .iter()
+                    .map(|component| percentage_weights(&component.counts, bug_percentage))
+                    .collect::<Vec<_>>();
+                let (bug_weight, free_weight) = weight_ratio(bug_percentage);
+                let interior = Probability {
+                    bugs: BigUint::from(bug_weight),
+                    total: BigUint::from(bug_weight + free_weight),
+                };
+                (weights, interior)
+            }
+            Bugs::Total(total) => {
+                let prefix = prefix_counts(components);
+                let all = prefix.last().unwrap();
+                let frontier = all.len() - 1;
+                let placements = self.placement_counts(frontier, total, false);
+                let weigh = |placements: &[BigUint]| {
+                    all.iter()
+                        .zip(placements.iter())
+                        .fold(BigUint::zero(), |total, (count, placements)| total.add(&count.mul(placements)))
+                };
+                let interior = Probability {
+                    bugs: weigh(&self.placement_counts(frontier, total, true)),
+                    total: weigh(&placements),
+                };
+                if interior.total.is_zero() {
+                    return Err(Inconsistency::TotalBugs);
                 }
+                // Solutions of all components but one, combined with all ways to place the rest of the bugs
+                let mut suffix = vec![BigUint::from(1)];
+                let mut weights = vec![Vec::new(); components.len()];
+                for i in (0..components.len()).rev() {
+                    let others = convolve(&prefix[i], &suffix);
+                    weights[i] = (0..components[i].counts.len())
+                        .map(|bugs| {
+                            others
+                                .iter()
+                                .enumerate()
+                                .fold(BigUint::zero(), |total, (other_bugs, count)| total.add(&count.mul(&placements[bugs + other_bugs])))
+                        })
+                        .collect();
+                    suffix = convolve(&suffix, &to_big(&components[i].counts));
+                }
+                (weights, interior)
+            }
+        };
+
+        for (index, field) in self.fields.iter().enumerate() {
+            if field.data == UNOPENED {
+                result[index] = Some(interior.clone());
             }
         }
-        result
+        for (component, weights) in components.iter().zip(weights.iter()) {
+            let weigh = |counts: &[u64]| {
+                weights
+                    .iter()
+                    .zip(counts.iter())
+                    .fold(BigUint::zero(), |total, (weight, &count)| total.add(&weight.mul_small(count)))
+            };
+            let total = weigh(&component.counts);
+            for (pos, &index) in component.cells.iter().enumerate() {
+                result[index as usize] = Some(Probability {
+                    bugs: weigh(&component.cell_counts[pos]),
+                    total: total.clone(),
+                });
+            }
+        }
+        Ok(result)
     }
+}
 
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
+impl fmt::Display for GameData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for y in 0..self.height {
             for x in 0..self.width {
                 let field = &self.fields[(y as usize)*(self.width as usize) + (x as usize)];
-                if field.data == BUG {
-                    result.push('X');
-                } else {
-                    result.push_str(".");
+                f.write_char(to_char(field.value()))?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+// Reads boards as written by Display, one row per line. Reading stops at an empty line or at a line
+// that starts with a letter other than X or F, so the state line that Game::print adds is skipped.
+impl FromStr for GameData {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .take_while(|line| line.chars().next().is_some_and(|c| c == 'X' || c == 'F' || !c.is_alphabetic()))
+            .collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if rows.is_empty() || rows.len() > u8::MAX as usize || width == 0 || width > u8::MAX as usize {
+            return Err(ParseError::Size);
+        }
+        let mut data = Vec::with_capacity(width * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(ParseError::RowLength(y as u8));
+            }
+            for (x, c) in row.chars().enumerate() {
+                data.push(from_char(c).ok_or(ParseError::Character(x as u8, y as u8))?);
+            }
+        }
+        Ok(GameData::new(width as u8, rows.len() as u8, data))
+    }
+}
+
+// Relative weight of a field being a bug vs bug-free, in lowest terms
+fn weight_ratio(bug_percentage: u8) -> (u64, u64) {
+    let bug = bug_percentage.min(100) as u64;
+    let free = 100 - bug;
+    let (mut a, mut b) = (bug, free);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    (bug / a, free / a)
+}
+
+// Weight of a single solution with k bugs when every field is a bug with bug_percentage chance, for every
+// k from 0 to counts.len() - 1, where counts[k] is the number of solutions with k bugs
+fn percentage_weights(counts: &[u64], bug_percentage: u8) -> Vec<BigUint> {
+    let (bug_weight, free_weight) = weight_ratio(bug_percentage);
+    let num_fields = counts.len() - 1;
+    let weigh = |bug_weight: u64, free_weight: u64| -> Vec<BigUint> {
+        (0..=num_fields)
+            .map(|bugs| {
+                // Only needed for numbers of bugs that have solutions, this gets expensive on large boards
+                if counts[bugs] == 0 {
+                    return BigUint::zero();
+                }
+                let mut weight = BigUint::from(1);
+                for (factor, times) in [(bug_weight, bugs), (free_weight, num_fields - bugs)] {
+                    if factor != 1 {
+                        for _ in 0..times {
+                            weight = weight.mul_small(factor);
+                        }
+                    }
+                }
+                weight
+            })
+            .collect()
+    };
+    let weights = weigh(bug_weight, free_weight);
+    if weights.iter().zip(counts.iter()).all(|(weight, &count)| weight.is_zero() || count == 0) {
+        // The opened fields need bugs that the bug percentage says can't exist (0% or 100%),
+        // fall back to treating all consistent solutions as equally likely.
+        return weigh(1, 1);
+    }
+    weights
+}
+
+// Pick an index with probability proportional to its weight, None if all weights are zero
+fn pick(weights: &[BigUint], r: &mut Rng) -> Option<usize> {
+    let total = weights.iter().fold(BigUint::zero(), |total, weight| total.add(weight));
+    if total.is_zero() {
+        return None;
+    }
+    let mut pick = total.random_below(r);
+    let mut index = 0;
+    while pick >= weights[index] {
+        pick = pick.sub(&weights[index]);
+        index += 1;
+    }
+    Some(index)
+}
+
+fn to_big(counts: &[u64]) -> Vec<BigUint> {
+    counts.iter().map(|&count| BigUint::from(count)).collect()
+}
+
+// Combine solution counts per number of bugs of two independent sets of fields
+fn convolve(a: &[BigUint], b: &[BigUint]) -> Vec<BigUint> {
+    let mut result = vec![BigUint::zero(); a.len() + b.len() - 1];
+    for (i, a) in a.iter().enumerate() {
+        if a.is_zero() {
+            continue;
+        }
+        for (j, b) in b.iter().enumerate() {
+            result[i + j] = result[i + j].add(&a.mul(b));
+        }
+    }
+    result
+}
+
+// prefix[i][k]: number of solutions with k bugs in total of the first i components
+fn prefix_counts(components: &[Component]) -> Vec<Vec<BigUint>> {
+    let mut prefix = vec![vec![BigUint::from(1)]];
+    for component in components {
+        let next = convolve(prefix.last().unwrap(), &to_big(&component.counts));
+        prefix.push(next);
+    }
+    prefix
+}
+
+// Binomial coefficients n choose k, for every k from 0 to n
+fn binomials(n: usize) -> Vec<BigUint> {
+    let mut result = vec![BigUint::from(1)];
+    for k in 0..n {
+        let next = result[k].mul_small((n - k) as u64).div_small((k + 1) as u64);
+        result.push(next);
+    }
+    result
+}
+
+// The solutions of a group of frontier fields: counts[k] is the number of consistent assignments with
+// k bugs and cell_counts[i][k] the number of those that have a bug in cells[i]
+#[derive(Clone)]
+struct Component {
+    cells: Vec<u16>,
+    counts: Vec<u64>,
+    cell_counts: Vec<Vec<u64>>,
+}
+
+// Backtracking search over bug assignments for lists of unopened fields
+struct FrontierSearch<'a> {
+    data: &'a GameData,
+    // For every opened field: the bugs around it that still need to be placed, and the
+    // number of unopened fields around it that haven't been decided yet
+    bugs_left: Vec<i16>,
+    undecided: Vec<u8>,
+    // How many more cells may be assigned before the search gives up
+    steps_left: u64,
+}
+
+impl<'a> FrontierSearch<'a> {
+    fn new(data: &'a GameData) -> Self {
+        let bugs_left = data
+            .fields
+            .iter()
+            .map(|field| if field.data < BUG { field.data as i16 - field.adjacent_bugs as i16 } else { 0 })
+            .collect();
+        let undecided = data.fields.iter().map(|field| field.impacted_by.len() as u8).collect();
+        FrontierSearch {
+            data,
+            bugs_left,
+            undecided,
+            steps_left: u64::MAX,
+        }
+    }
+
+    fn is_ok(&self, index: usize) -> bool {
+        self.bugs_left[index] >= 0 && self.bugs_left[index] <= self.undecided[index] as i16
+    }
+
+    // Returns whether all opened fields around the cell can still be satisfied
+    fn assign(&mut self, cell: u16, is_bug: bool) -> bool {
+        let mut all_ok = true;
+        for &opened in self.data.fields[cell as usize].impacts.iter() {
+            let opened = opened as usize;
+            self.undecided[opened] -= 1;
+            if is_bug {
+                self.bugs_left[opened] -= 1;
+            }
+            all_ok = all_ok && self.is_ok(opened);
+        }
+        all_ok
+    }
+
+    fn unassign(&mut self, cell: u16, is_bug: bool) {
+        for &opened in self.data.fields[cell as usize].impacts.iter() {
+            let opened = opened as usize;
+            self.undecided[opened] += 1;
+            if is_bug {
+                self.bugs_left[opened] += 1;
+            }
+        }
+    }
+
+    // Call visit for every assignment of bugs to cells that is consistent with the opened fields,
+    // together with the number of bugs in it. Stops early when visit returns true.
+    fn search<F: FnMut(&[bool], usize) -> bool>(&mut self, cells: &[u16], mut visit: F) {
+        let mut state = SearchState::new(cells.len());
+        while state.advance(self, cells) {
+            if visit(&state.is_bug, state.bugs) {
+                // Leave the counts as we found them for the next search
+                for (&cell, &is_bug) in cells.iter().zip(state.is_bug.iter()) {
+                    self.unassign(cell, is_bug);
+                }
+                return;
+            }
+        }
+    }
+}
+
+// Where a depth-first search over the assignments of bugs to cells is, so it can be paused after every
+// solution. The stack is explicit: is_bug holds the values for cells 0..pos, and cells that were set to
+// false still need to be tried as a bug when we backtrack to them. This keeps the stack use constant,
+// whatever the size of the board.
+struct SearchState {
+    is_bug: Vec<bool>,
+    pos: usize,
+    bugs: usize,
+    // The value to try next for cells[pos], None if both have been tried
+    next: Option<bool>,
+}
+
+impl SearchState {
+    fn new(num_cells: usize) -> Self {
+        SearchState {
+            is_bug: vec![false; num_cells],
+            pos: 0,
+            bugs: 0,
+            next: Some(false),
+        }
+    }
+
+    // Continue to the next assignment of all cells that is consistent with the opened fields, false
+    // if there are no more or the search ran out of steps. After true, is_bug and bugs hold the assignment and all cells are assigned
+    // in search.
+    fn advance(&mut self, search: &mut FrontierSearch, cells: &[u16]) -> bool {
+        loop {
+            match self.next {
+                Some(_) if self.pos < cells.len() && search.steps_left == 0 => return false,
+                Some(bug) if self.pos < cells.len() => {
+                    search.steps_left -= 1;
+                    let pos = self.pos;
+                    self.is_bug[pos] = bug;
+                    let ok = search.assign(cells[pos], bug);
+                    self.bugs += bug as usize;
+                    if ok {
+                        self.pos += 1;
+                        self.next = Some(false);
+                        continue;
+                    }
+                    search.unassign(cells[pos], bug);
+                    self.bugs -= bug as usize;
+                    self.next = if bug { None } else { Some(true) };
+                }
+                Some(_) => {
+                    self.next = None;
+                    return true;
+                }
+                None => {
+                    if self.pos == 0 {
+                        return false;
+                    }
+                    self.pos -= 1;
+                    let pos = self.pos;
+                    search.unassign(cells[pos], self.is_bug[pos]);
+                    self.bugs -= self.is_bug[pos] as usize;
+                    self.next = if self.is_bug[pos] { None } else { Some(true) };
                 }
             }
-            result.push_str("\n");
         }
-        result
     }
+}
 
+// Every consistent way to fill in the unopened fields of a board, see GameData::layouts
+pub struct Layouts<'a> {
+    data: &'a GameData,
+    search: FrontierSearch<'a>,
+    cells: Vec<u16>,
+    state: SearchState,
 }
 
+impl Iterator for Layouts<'_> {
+    type Item = GameData;
+
+    fn next(&mut self) -> Option<GameData> {
+        if !self.state.advance(&mut self.search, &self.cells) {
+            return None;
+        }
+        let mut result = self.data.clone();
+        for (&index, &is_bug) in self.cells.iter().zip(self.state.is_bug.iter()) {
+            result.decide(index, is_bug);
+        }
+        Some(result)
+    }
+}
 
 #[cfg(test)]
 #[allow(non_snake_case)]
@@ -296,7 +1147,7 @@ mod tests {
 
         let z = UNOPENED_BUGFREE;
 
-        let filled_in = data.fill_in(63515, 20);
+        let filled_in = data.fill_in(63515, Bugs::Percentage(20)).unwrap();
 
         assert_eq!(filled_in.fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
             X, X, z, z,
@@ -305,22 +1156,22 @@ mod tests {
             X, X, X, 1
         ]);
 
-        let filled_in = GameData::new(5,5, vec![o; 25]).fill_in(666132615, 20);
+        let filled_in = GameData::new(5,5, vec![o; 25]).fill_in(666132615, Bugs::Percentage(20)).unwrap();
         assert_eq!(filled_in.fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
-            z, X, z, X, z,
-            z, z, z, z, z,
             z, z, z, z, z,
-            z, X, X, z, z,
-            z, X, z, z, z
+            z, z, z, z, X,
+            z, X, z, z, z,
+            X, X, z, z, X,
+            z, z, z, z, z
         ]);
 
-        let filled_in = GameData::new(5,5, vec![o; 25]).fill_in(666132615, 80);
+        let filled_in = GameData::new(5,5, vec![o; 25]).fill_in(666132615, Bugs::Percentage(80)).unwrap();
         assert_eq!(filled_in.fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
-            X, X, z, X, X,
-            X, X, X, X, X,
-            z, X, X, X, z,
+            z, X, z, X, X,
             X, X, X, X, X,
             X, X, X, X, z,
+            X, X, X, X, X,
+            X, X, z, X, X,
         ]);
 
         let data = GameData::new(
@@ -331,19 +1182,19 @@ mod tests {
             o, o, o, 1
         ]);
 
-        let filled_in = data.fill_in(666132615, 25);
+        let filled_in = data.fill_in(666132615, Bugs::Percentage(25)).unwrap();
         assert_eq!(filled_in.fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
-            X, X, z, z,
+            X, z, X, z,
             X, 4, 1, z,
             X, z, z, z,
             z, z, X, 1
         ]);
 
-        assert_eq!(data.fill_in(13371, 25).fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
-            X, z, X, z,
-            X, 4, 1, z,
+        assert_eq!(data.fill_in(13371, Bugs::Percentage(25)).unwrap().fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
             X, z, z, z,
-            z, X, X, 1
+            X, 4, 1, z,
+            X, z, X, z,
+            z, z, z, 1
         ]);
     }
   
@@ -360,13 +1211,13 @@ mod tests {
             o, o, z, o,
             o, o, o, o,
         ]);
-        assert_eq!(data.fill_in(13371, 50).fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
-            z, X, X, z,
+        assert_eq!(data.fill_in(13371, Bugs::Percentage(50)).unwrap().fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
             X, z, z, z,
-            z, X, z, z,
-            z, X, X, X,
+            X, z, z, X,
+            X, X, z, z,
+            X, z, z, X,
         ]);
-        assert_eq!(data.fill_in(13371, 100).fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
+        assert_eq!(data.fill_in(13371, Bugs::Percentage(100)).unwrap().fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
             X, X, X, X,
             X, X, X, X,
             X, X, z, X,
@@ -374,4 +1225,615 @@ mod tests {
         ]);
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_generate_weighted() {
+        let o = UNOPENED;
+        // Either the middle field is a bug, or both outer fields are
+        let data = GameData::new(5, 1, vec![o, 1, o, 1, o]);
+        // With 20% bug chance, one bug (0.2 * 0.8 * 0.8) is 4 times as likely as two bugs (0.2 * 0.2 * 0.8)
+        let middle_bugs = (0..1000).filter(|&seed| data.fill_in(seed, Bugs::Percentage(20)).unwrap().get(2, 0).data == BUG).count();
+        assert!((760..840).contains(&middle_bugs), "{}", middle_bugs);
+        // With 50% bug chance, the two solutions are equally likely
+        let middle_bugs = (0..1000).filter(|&seed| data.fill_in(seed, Bugs::Percentage(50)).unwrap().get(2, 0).data == BUG).count();
+        assert!((450..550).contains(&middle_bugs), "{}", middle_bugs);
+        // With 0% bug chance we still need to find a solution
+        let filled_in = data.fill_in(1, Bugs::Percentage(0)).unwrap();
+        assert_eq!(filled_in.get(0, 0).data == BUG, filled_in.get(4, 0).data == BUG);
+        assert_ne!(filled_in.get(0, 0).data == BUG, filled_in.get(2, 0).data == BUG);
+    }
+
+    #[test]
+    fn test_bug_probabilities() {
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        let data = GameData::new(7, 1, vec![o, 1, o, 1, o, o, z]);
+        let per_million = data
+            .bug_probabilities(Bugs::Percentage(20))
+            .unwrap()
+            .iter()
+            .map(|probability| probability.as_ref().map(|probability| probability.per_million()))
+            .collect::<Vec<_>>();
+        assert_eq!(per_million, vec![
+            Some(200_000), None, Some(800_000), None, Some(200_000), Some(200_000), Some(0)
+        ]);
+
+        let probabilities = data.bug_probabilities(Bugs::Percentage(50)).unwrap();
+        assert_eq!(probabilities[2].as_ref().unwrap().per_million(), 500_000);
+        assert_eq!(probabilities[5].as_ref().unwrap().per_million(), 500_000);
+
+        // Bugs can't exist at 0%, so fill_in treats all solutions as equally likely
+        let probabilities = data.bug_probabilities(Bugs::Percentage(0)).unwrap();
+        assert_eq!(probabilities[0].as_ref().unwrap().per_million(), 500_000);
+        assert_eq!(probabilities[2].as_ref().unwrap().per_million(), 500_000);
+        assert_eq!(probabilities[5].as_ref().unwrap().per_million(), 0);
+
+        // Corner 3 forces all of its neighbours to be bugs
+        let data = GameData::new(3, 3, vec![
+            3, o, o,
+            o, o, o,
+            o, o, o,
+        ]);
+        let probabilities = data.bug_probabilities(Bugs::Percentage(20)).unwrap();
+        for index in [1, 3, 4] {
+            assert_eq!(probabilities[index].as_ref().unwrap().per_million(), 1_000_000);
+        }
+        assert_eq!(probabilities[8].as_ref().unwrap().per_million(), 200_000);
+    }
+
+    #[test]
+    fn test_count_layouts() {
+        let X = BUG;
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        let boards = [
+            GameData::new(7, 1, vec![o, 1, o, 1, o, o, z]),
+            GameData::new(4, 3, vec![
+                o, o, o, o,
+                1, 2, o, o,
+                0, 1, o, X,
+            ]),
+        ];
+        for data in boards.iter() {
+            let unopened = data.fields.iter().filter(|field| field.data == UNOPENED).count() as u16;
+            let brute_force = |bugs| brute_force_probabilities(data, bugs).into_iter().flatten().next().unwrap().total;
+            // At 50% every layout has the same weight 50^n
+            let all = data.count_layouts().unwrap();
+            assert_eq!(all.mul(&BigUint::from(50).pow(unopened as usize)), brute_force(Bugs::Percentage(50)));
+            for bug_percentage in [0, 20, 100] {
+                assert_eq!(data.count_layouts_with(Bugs::Percentage(bug_percentage)).unwrap(), brute_force(Bugs::Percentage(bug_percentage)));
+            }
+            let mut sum = BigUint::zero();
+            for total in 0..=unopened + 1 {
+                let actual = data.count_layouts_with(Bugs::Total(total)).unwrap();
+                assert_eq!(actual, brute_force(Bugs::Total(total)));
+                sum = sum.add(&actual);
+            }
+            assert_eq!(sum, all);
+        }
+        assert_eq!(boards[0].count_layouts().unwrap(), BigUint::from(4));
+        assert_eq!(GameData::new(3, 1, vec![o, 2, o]).count_layouts().unwrap(), BigUint::from(1));
+        assert_eq!(GameData::new(2, 1, vec![0, 2]).count_layouts(), Err(Inconsistency::Fields(vec![(1, 0)])));
+    }
+
+    #[test]
+    fn test_layouts() {
+        let X = BUG;
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        let data = GameData::new(4, 3, vec![
+            o, o, o, z,
+            1, 2, o, o,
+            0, 1, o, X,
+        ]);
+        let layouts = data.layouts().collect::<Vec<_>>();
+        assert_eq!(BigUint::from(layouts.len() as u64), data.count_layouts().unwrap());
+        for (i, layout) in layouts.iter().enumerate() {
+            assert_consistent(layout);
+            assert_eq!(layout.get(3, 0).data, UNOPENED_BUGFREE);
+            assert_eq!(layout.get(3, 2).data, BUG);
+            assert!(layouts[..i].iter().all(|other| other.data() != layout.data()));
+        }
+        for total in 1..=7 {
+            let count = layouts.iter().filter(|layout| layout.num_bugs() == total).count();
+            assert_eq!(BigUint::from(count as u64), data.count_layouts_with(Bugs::Total(total as u16)).unwrap());
+        }
+
+        // Only the ones that are needed are found
+        let data = GameData::new(30, 16, vec![o; 480]);
+        assert_eq!(data.layouts().take(3).count(), 3);
+        let data = GameData::new(1, 1, vec![0]);
+        assert_eq!(data.layouts().map(|layout| layout.data()).collect::<Vec<_>>(), vec![vec![0]]);
+        let data = GameData::new(3, 1, vec![o, 0, X]);
+        assert_eq!(data.layouts().count(), 0);
+    }
+
+    #[test]
+    fn test_generate_total() {
+        let X = BUG;
+        let o = UNOPENED;
+        for seed in 0..20 {
+            let filled_in = GameData::new(5, 5, vec![o; 25]).fill_in(seed, Bugs::Total(10)).unwrap();
+            assert_eq!(filled_in.num_bugs(), 10);
+        }
+
+        // Either the middle field is a bug, or both outer fields are
+        let data = GameData::new(7, 1, vec![o, 1, o, 1, o, o, o]);
+        assert_eq!(data.fill_in(1, Bugs::Total(1)).unwrap().fields.iter().map(|field| field.data).collect::<Vec<u8>>(), vec![
+            UNOPENED_BUGFREE, 1, X, 1, UNOPENED_BUGFREE, UNOPENED_BUGFREE, UNOPENED_BUGFREE
+        ]);
+        // With 2 bugs, the middle one has 2 choices for the other bug, the outer ones only 1
+        let middle_bugs = (0..900).filter(|&seed| {
+            let filled_in = data.fill_in(seed, Bugs::Total(2)).unwrap();
+            assert_eq!(filled_in.num_bugs(), 2);
+            filled_in.get(2, 0).data == BUG
+        }).count();
+        assert!((550..650).contains(&middle_bugs), "{}", middle_bugs);
+
+        let per_million = data
+            .bug_probabilities(Bugs::Total(2))
+            .unwrap()
+            .iter()
+            .map(|probability| probability.as_ref().map(|probability| probability.per_million()))
+            .collect::<Vec<_>>();
+        assert_eq!(per_million, vec![
+            Some(333_333), None, Some(666_666), None, Some(333_333), Some(333_333), Some(333_333)
+        ]);
+
+        assert_eq!(GameData::new(3, 1, vec![X, o, o]).remaining_bugs(2), 1);
+        assert_eq!(GameData::new(3, 1, vec![X, o, X]).remaining_bugs(1), 0);
+        let f = FLAGGED;
+        assert_eq!(GameData::new(4, 1, vec![f, 1, o, o]).remaining_bugs(3), 2);
+        assert_eq!(GameData::new(4, 1, vec![f, 1, f, f]).remaining_bugs(2), 0);
+    }
+
+    #[test]
+    fn test_inconsistent() {
+        let o = UNOPENED;
+        // The 1 has no unopened field left for its bug
+        let data = GameData::new(3, 1, vec![1, 0, o]);
+        assert_eq!(data.fill_in(1, Bugs::Percentage(20)).err(), Some(Inconsistency::Fields(vec![(0, 0)])));
+
+        // Each number on its own is fine, but they disagree about the middle field
+        let data = GameData::new(4, 1, vec![0, o, 1, o]);
+        assert!(data.fill_in(1, Bugs::Percentage(20)).is_ok());
+        let data = GameData::new(3, 1, vec![0, o, 1]);
+        assert_eq!(data.fill_in(1, Bugs::Percentage(20)).err(), Some(Inconsistency::Fields(vec![(0, 0), (2, 0)])));
+        assert_eq!(data.bug_probabilities(Bugs::Percentage(20)).err(), Some(Inconsistency::Fields(vec![(0, 0), (2, 0)])));
+
+        // At most 2 bugs fit
+        let data = GameData::new(5, 1, vec![o, 1, o, 1, o]);
+        assert_eq!(data.fill_in(1, Bugs::Total(3)).err(), Some(Inconsistency::TotalBugs));
+        assert_eq!(data.bug_probabilities(Bugs::Total(3)).err(), Some(Inconsistency::TotalBugs));
+        assert_eq!(GameData::new(2, 1, vec![BUG, o]).fill_in(1, Bugs::Total(0)).err(), Some(Inconsistency::TotalBugs));
+    }
+
+    #[test]
+    fn test_too_complex() {
+        // Every field with an even x and y opened, with the bugs on the fields with an odd x and y. The
+        // numbers overlap in one big group, with far too many ways to place the bugs on a big board.
+        let board = |size: u8| {
+            let mut fields = Vec::new();
+            for y in 0..size as i16 {
+                for x in 0..size as i16 {
+                    let is_odd = |x: i16, y: i16| (0..size as i16).contains(&x) && (0..size as i16).contains(&y) && x % 2 == 1 && y % 2 == 1;
+                    let around = [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().filter(|(dx, dy)| is_odd(x + dx, y + dy)).count();
+                    fields.push(if x % 2 == 0 && y % 2 == 0 { around as u8 } else { UNOPENED });
+                }
+            }
+            GameData::new(size, size, fields)
+        };
+        assert!(board(6).fill_in(1, Bugs::Percentage(30)).is_ok());
+        let data = board(16);
+        assert_eq!(data.fill_in(1, Bugs::Percentage(30)).err(), Some(Inconsistency::TooComplex));
+        assert_eq!(data.bug_probabilities(Bugs::Total(64)).err(), Some(Inconsistency::TooComplex));
+        assert_eq!(data.fill_in_forgiving(1, Bugs::Percentage(30), 1, 1).err(), Some(Inconsistency::TooComplex));
+    }
+
+    #[test]
+    fn test_validate() {
+        let X = BUG;
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        assert_eq!(GameData::new(3, 2, vec![
+            X, 2, o,
+            o, o, z,
+        ]).validate(), Ok(()));
+        assert_eq!(GameData::new(3, 1, vec![X, 1, X]).validate(), Err(InvalidBoard::Inconsistent(Inconsistency::Fields(vec![(1, 0)]))));
+        assert_eq!(GameData::new(3, 1, vec![0, o, 1]).validate(), Err(InvalidBoard::Inconsistent(Inconsistency::Fields(vec![(0, 0), (2, 0)]))));
+        assert_eq!(GameData::new(3, 1, vec![13, o, 15]).validate(), Err(InvalidBoard::Values(vec![(0, 0), (2, 0)])));
+        assert_eq!(GameData::new(3, 1, vec![FLAGGED, 1, o]).validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_text() {
+        let X = BUG;
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        let data = GameData::new(4, 2, vec![
+            X, 2, o, 0,
+            o, z, 8, 1,
+        ]);
+        assert_eq!(data.to_string(), "X2.0\n.-81\n");
+        assert_eq!(data.to_string().parse::<GameData>().unwrap().data(), data.data());
+
+        // The way the game shows a board to the player, with the state on the last line
+        let view = "1X  \n    \nLost\n".parse::<GameData>().unwrap();
+        assert_eq!((view.width(), view.height()), (4, 2));
+        assert_eq!(view.data(), vec![1, X, o, o, o, o, o, o]);
+        let f = FLAGGED;
+        let view = "F1 \n F1\nPlaying\n".parse::<GameData>().unwrap();
+        assert_eq!(view.data(), vec![f, 1, o, o, f, 1]);
+        assert_eq!(view.to_string(), "F1.\n.F1\n");
+
+        assert_eq!("..\n...\n".parse::<GameData>().err(), Some(ParseError::RowLength(1)));
+        assert_eq!("..\n.a\n".parse::<GameData>().err(), Some(ParseError::Character(1, 1)));
+        assert_eq!("..\n.9\n".parse::<GameData>().err(), Some(ParseError::Character(1, 1)));
+        assert_eq!("".parse::<GameData>().err(), Some(ParseError::Size));
+        assert_eq!("Playing\n".parse::<GameData>().err(), Some(ParseError::Size));
+    }
+
+    #[test]
+    fn test_flags() {
+        let o = UNOPENED;
+        let f = FLAGGED;
+        // A flagged field is unopened, so here it's one of the two fields that can hold the bug of the 1
+        let data = "F1.\n...\n".parse::<GameData>().unwrap();
+        assert_eq!(data.data(), vec![f, 1, o, o, o, o]);
+        assert!(data.is_flagged(0, 0) && !data.is_flagged(2, 0));
+        assert!(data.validate().is_ok());
+        let probabilities = data.bug_probabilities(Bugs::Total(1)).unwrap();
+        assert_eq!(probabilities[0].as_ref().unwrap().per_million(), 200_000);
+        let mut bug_positions = Vec::new();
+        for seed in 0..50 {
+            let filled_in = data.fill_in(seed, Bugs::Total(1)).unwrap();
+            assert!(!filled_in.data().contains(&FLAGGED));
+            bug_positions.push(filled_in.data().iter().position(|&value| value == BUG).unwrap());
+        }
+        assert!(bug_positions.contains(&0));
+        // Opening a flagged field removes the flag
+        let mut opened = data.clone();
+        opened.set_data(0, 0, 1);
+        assert_eq!(opened.data()[0], 1);
+    }
+
+    #[test]
+    fn test_reveal() {
+        let o = UNOPENED;
+        let data = GameData::new(3, 2, vec![
+            o, o, o,
+            1, 1, o,
+        ]);
+        let probabilities = data.bug_probabilities(Bugs::Percentage(20)).unwrap();
+        let mut bugs = 0;
+        for seed in 0..1000 {
+            let (filled_in, probability) = data.reveal(seed, Bugs::Percentage(20), 0, 0).unwrap();
+            assert_eq!(Some(probability), probabilities[0]);
+            assert_consistent(&filled_in);
+            if filled_in.get(0, 0).data == BUG {
+                bugs += 1;
+            }
+        }
+        let expected = probabilities[0].as_ref().unwrap().per_million() as i32 / 1000;
+        assert!((bugs - expected).abs() < 50, "{} bugs, expected {}", bugs, expected);
+
+        // The 0 is certainly safe, fields known to be bug-free as well
+        let (_, probability) = data.reveal(0, Bugs::Percentage(20), 0, 1).unwrap();
+        assert!(probability.bugs.is_zero());
+        let data = GameData::new(2, 1, vec![UNOPENED_BUGFREE, o]);
+        let (filled_in, probability) = data.reveal(0, Bugs::Total(1), 0, 0).unwrap();
+        assert!(probability.bugs.is_zero());
+        assert_eq!(filled_in.data(), vec![UNOPENED_BUGFREE, BUG]);
+    }
+
+    #[test]
+    fn test_generate_evil() {
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        let data = GameData::new(4, 3, vec![
+            0, 1, o, o,
+            0, 1, o, o,
+            0, 1, o, o,
+        ]);
+        for seed in 0..20 {
+            // The fields next to the ones share a single bug, so each can be one
+            assert_eq!(data.fill_in_evil(seed, Bugs::Percentage(20), 2, 1).unwrap().0.get(2, 1).data, BUG);
+            // Off the frontier a bug is always possible, even if it's very unlikely
+            assert_eq!(data.fill_in_evil(seed, Bugs::Percentage(1), 3, 0).unwrap().0.get(3, 0).data, BUG);
+            assert_consistent(&data.fill_in_evil(seed, Bugs::Total(3), 3, 2).unwrap().0);
+            assert_eq!(data.fill_in_evil(seed, Bugs::Total(3), 3, 2).unwrap().0.get(3, 2).data, BUG);
+        }
+
+        // The chance that was applied is certain, not the odds of a normal game
+        assert_eq!(data.fill_in_evil(0, Bugs::Percentage(20), 2, 1).unwrap().1, Probability::certain(true));
+
+        // Only one bug in total, and it has to be next to the ones
+        let (filled_in, probability) = data.fill_in_evil(1, Bugs::Total(1), 3, 1).unwrap();
+        assert!(probability.bugs.is_zero());
+        assert_eq!(filled_in.get(3, 1).data, UNOPENED_BUGFREE);
+        assert_consistent(&filled_in);
+
+        // The ones already have their bug, so the field next to it is forced to be safe. Fields that are
+        // known to be bug-free stay that way.
+        let X = BUG;
+        let data = GameData::new(2, 3, vec![
+            1, 1,
+            X, o,
+            z, o,
+        ]);
+        assert_eq!(data.fill_in_evil(0, Bugs::Percentage(20), 1, 1).unwrap().0.get(1, 1).data, UNOPENED_BUGFREE);
+        assert_eq!(data.fill_in_evil(0, Bugs::Percentage(20), 0, 2).unwrap().0.get(0, 2).data, UNOPENED_BUGFREE);
+        assert_eq!(data.fill_in_evil(0, Bugs::Percentage(20), 1, 2).unwrap().0.get(1, 2).data, BUG);
+    }
+
+    #[test]
+    fn test_generate_forgiving() {
+        let o = UNOPENED;
+        // A 50/50: one of the two fields next to the ones is a bug, and nothing else is unopened
+        let data = GameData::new(2, 3, vec![
+            o, o,
+            1, 1,
+            0, 0,
+        ]);
+        for seed in 0..20 {
+            for x in 0..2 {
+                for bugs in [Bugs::Percentage(20), Bugs::Total(1)] {
+                    let (filled_in, probability) = data.fill_in_forgiving(seed, bugs, x, 0).unwrap();
+                    assert_eq!(filled_in.get(x, 0).data, UNOPENED_BUGFREE);
+                    assert_eq!(probability, Probability::certain(false));
+                    assert_consistent(&filled_in);
+                }
+            }
+        }
+
+        // No safe move, but the guessed field is a bug in every layout
+        let data = GameData::new(2, 1, vec![o, 1]);
+        assert_eq!(data.fill_in_forgiving(0, Bugs::Percentage(20), 0, 0).unwrap().0.get(0, 0).data, BUG);
+
+        // The field left of the 0 is safe, so guessing elsewhere gets the usual treatment
+        let data = GameData::new(3, 3, vec![
+            o, o, o,
+            o, o, 0,
+            o, o, o,
+        ]);
+        let bugs = (0..100)
+            .filter(|&seed| data.fill_in_forgiving(seed, Bugs::Percentage(50), 0, 1).unwrap().0.get(0, 1).data == BUG)
+            .count();
+        assert!(bugs > 20 && bugs < 80);
+        assert_eq!(data.fill_in_forgiving(0, Bugs::Percentage(50), 0, 1).unwrap().1.per_million(), 500_000);
+    }
+
+    // Exact bug probabilities by trying every possible layout, only feasible for small boards
+    fn brute_force_probabilities(data: &GameData, bugs: Bugs) -> Vec<Option<Probability>> {
+        let unopened: Vec<usize> = (0..data.fields.len()).filter(|&index| data.fields[index].data == UNOPENED).collect();
+        let mut bug_weights = vec![BigUint::zero(); data.fields.len()];
+        let mut total = BigUint::zero();
+        for layout in 0u32..(1 << unopened.len()) {
+            let mut values = data.fields.iter().map(|field| field.data).collect::<Vec<u8>>();
+            for (bit, &index) in unopened.iter().enumerate() {
+                values[index] = if layout & (1 << bit) != 0 { BUG } else { UNOPENED_BUGFREE };
+            }
+            let filled_in = GameData::new(data.width, data.height, values);
+            if filled_in.fields.iter().any(|field| field.data < BUG && field.data != field.adjacent_bugs) {
+                continue;
+            }
+            let num_bugs = layout.count_ones() as usize;
+            let weight = match bugs {
+                Bugs::Percentage(bug_percentage) => {
+                    let mut weight = BigUint::from(1);
+                    for _ in 0..num_bugs {
+                        weight = weight.mul_small(bug_percentage as u64);
+                    }
+                    for _ in num_bugs..unopened.len() {
+                        weight = weight.mul_small(100 - bug_percentage as u64);
+                    }
+                    weight
+                }
+                Bugs::Total(total) => BigUint::from((filled_in.num_bugs() == total as usize) as u64),
+            };
+            total = total.add(&weight);
+            for &index in unopened.iter() {
+                if filled_in.fields[index].data == BUG {
+                    bug_weights[index] = bug_weights[index].add(&weight);
+                }
+            }
+        }
+        data.fields
+            .iter()
+            .zip(bug_weights)
+            .map(|(field, bugs)| (field.data == UNOPENED).then(|| Probability { bugs, total: total.clone() }))
+            .collect()
+    }
+
+    fn assert_same_probabilities(actual: &[Option<Probability>], expected: &[Option<Probability>]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            if let (Some(actual), Some(expected)) = (actual, expected) {
+                assert_eq!(actual.bugs.mul(&expected.total), expected.bugs.mul(&actual.total));
+            }
+        }
+    }
+
+    #[test]
+    fn test_components() {
+        let o = UNOPENED;
+        let data = GameData::new(6, 3, vec![
+            1, o, o, o, o, 1,
+            o, o, o, o, o, o,
+            o, 1, o, o, 2, o,
+        ]);
+        // The 1 at the top left and bottom left share fields, the 1 and 2 on the right don't
+        assert_eq!(data.frontier_components().iter().map(Vec::len).collect::<Vec<_>>(), vec![6, 6]);
+
+        for bugs in [Bugs::Percentage(20), Bugs::Percentage(65), Bugs::Total(3), Bugs::Total(5)] {
+            assert_same_probabilities(&data.bug_probabilities(bugs).unwrap(), &brute_force_probabilities(&data, bugs));
+            for seed in 0..20 {
+                let filled_in = data.fill_in(seed, bugs).unwrap();
+                assert_consistent(&filled_in);
+                if let Bugs::Total(total) = bugs {
+                    assert_eq!(filled_in.num_bugs(), total as usize);
+                }
+            }
+        }
+    }
+
+    // Open a field on board the way the game does, using the bugs in solution, and flood fill around zeros
+    fn open(board: &mut GameData, solution: &GameData, x: u8, y: u8) {
+        let count = solution.get(x, y).adjacent_bugs;
+        board.set_data(x, y, count);
+        if count == 0 {
+            let width = board.width as usize;
+            for index in board.surrounding_fields(x, y) {
+                let (other_x, other_y) = ((index % width) as u8, (index / width) as u8);
+                if !is_open(board.get(other_x, other_y).data) {
+                    open(board, solution, other_x, other_y);
+                }
+            }
+        }
+    }
+
+    fn assert_consistent(filled_in: &GameData) {
+        assert!(filled_in.fields.iter().all(|field| field.data != UNOPENED));
+        assert_eq!(GameData::new(filled_in.width, filled_in.height, filled_in.data()).validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_generate_max_size_small_stack() {
+        // Open every bug-free field of a maximal board: the remaining fields form a frontier of
+        // thousands of cells that the search has to go through in one go
+        let solution = GameData::new(255, 255, vec![UNOPENED; 255 * 255]).fill_in(5, Bugs::Percentage(20)).unwrap();
+        let data = solution
+            .fields
+            .iter()
+            .map(|field| if field.data == BUG { UNOPENED } else { field.adjacent_bugs })
+            .collect();
+        let board = GameData::new(255, 255, data);
+        assert!(board.frontier_components().iter().map(Vec::len).sum::<usize>() > 10000);
+
+        let filled_in = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || board.fill_in(6, Bugs::Percentage(20)).unwrap())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_consistent(&filled_in);
+        assert_eq!(filled_in.num_bugs(), solution.num_bugs());
+    }
+
+    #[test]
+    fn test_generate_large() {
+        let o = UNOPENED;
+        let z = UNOPENED_BUGFREE;
+        for (width, height) in [(9, 9), (16, 16), (30, 16)] {
+            for seed in 0..5 {
+                // Start in the middle with a safe area around the first click, like the game does
+                let (x, y) = (width / 2, height / 2);
+                let mut start = GameData::new(width, height, vec![o; width as usize * height as usize]);
+                for index in start.surrounding_fields(x, y) {
+                    start.fields[index].data = z;
+                }
+                start.set_data(x, y, z);
+                let solution = start.fill_in(seed, Bugs::Percentage(20)).unwrap();
+                assert_consistent(&solution);
+
+                let mut board = GameData::new(width, height, vec![o; width as usize * height as usize]);
+                open(&mut board, &solution, x, y);
+                let board = GameData::new(width, height, board.fields.iter().map(|field| field.data).collect());
+                assert!(board.num_open >= 9);
+
+                let filled_in = board.fill_in(seed + 1000, Bugs::Percentage(20)).unwrap();
+                assert_consistent(&filled_in);
+                let probabilities = board.bug_probabilities(Bugs::Percentage(20)).unwrap();
+                assert_eq!(probabilities.len(), width as usize * height as usize);
+            }
+        }
+    }
+
+    // A board that can be filled in, with the layout it was made from: some of the bug-free fields of a
+    // random layout are opened, some are known to be bug-free and the rest is left unopened
+    fn consistent_board() -> impl proptest::strategy::Strategy<Value = (GameData, GameData)> {
+        use proptest::prelude::*;
+        (1u8..=8, 1u8..=8, 0u8..=60)
+            .prop_flat_map(|(width, height, bug_percentage)| {
+                let fields = prop::collection::vec((0u8..100, 0u8..3), width as usize * height as usize);
+                (Just(width), Just(height), Just(bug_percentage), fields)
+            })
+            .prop_map(|(width, height, bug_percentage, fields)| {
+                let layout: Vec<u8> = fields.iter().map(|&(r, _)| if r < bug_percentage { BUG } else { UNOPENED_BUGFREE }).collect();
+                let layout = GameData::new(width, height, layout);
+                let data = fields
+                    .iter()
+                    .zip(layout.fields.iter())
+                    .map(|(&(_, kind), field)| match (field.data, kind) {
+                        (BUG, _) | (_, 0) => UNOPENED,
+                        (_, 1) => UNOPENED_BUGFREE,
+                        _ => field.adjacent_bugs,
+                    })
+                    .collect();
+                (GameData::new(width, height, data), layout)
+            })
+    }
+
+    // What every way of filling in a board has to do: only decide the unopened fields, keep the
+    // fields that are known to be bug-free, and agree with all opened fields
+    fn check_filled_in(board: &GameData, filled_in: &GameData) {
+        assert_eq!((filled_in.width, filled_in.height), (board.width, board.height));
+        for (before, after) in board.data().into_iter().zip(filled_in.data()) {
+            match before {
+                UNOPENED => assert!(after == BUG || after == UNOPENED_BUGFREE),
+                _ => assert_eq!(after, before),
+            }
+        }
+        assert_consistent(filled_in);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_fill_in((board, layout) in consistent_board(), seed: u64, bug_percentage in 0u8..=100) {
+            check_filled_in(&board, &board.fill_in(seed, Bugs::Percentage(bug_percentage)).unwrap());
+            let total = layout.num_bugs() as u16;
+            let filled_in = board.fill_in(seed, Bugs::Total(total)).unwrap();
+            check_filled_in(&board, &filled_in);
+            proptest::prop_assert_eq!(filled_in.num_bugs(), total as usize);
+        }
+
+        #[test]
+        fn prop_fill_in_guess((board, layout) in consistent_board(), seed: u64, pick: usize) {
+            let unopened: Vec<usize> = (0..board.fields.len()).filter(|&index| !is_open(board.fields[index].data)).collect();
+            proptest::prop_assume!(!unopened.is_empty());
+            let (x, y) = board.position(unopened[pick % unopened.len()]);
+            for bugs in [Bugs::Percentage(20), Bugs::Total(layout.num_bugs() as u16)] {
+                check_filled_in(&board, &board.fill_in_evil(seed, bugs, x, y).unwrap().0);
+                let (forgiven, chance) = board.fill_in_forgiving(seed, bugs, x, y).unwrap();
+                check_filled_in(&board, &forgiven);
+                // A guess is forgiven exactly when there was no safe move and it's not certainly a bug
+                let deduction = crate::deduce::deduce(&board, bugs).unwrap();
+                if deduction.safe.is_empty() && !deduction.bugs.contains(&(x, y)) {
+                    proptest::prop_assert_eq!(forgiven.get(x, y).data, UNOPENED_BUGFREE);
+                    proptest::prop_assert_eq!(chance, Probability::certain(false));
+                } else {
+                    proptest::prop_assert_eq!(chance, board.bug_probability(bugs, x, y).unwrap());
+                }
+                let (filled_in, probability) = board.reveal(seed, bugs, x, y).unwrap();
+                check_filled_in(&board, &filled_in);
+                proptest::prop_assert!(probability.bugs <= probability.total);
+            }
+        }
+
+        #[test]
+        fn prop_bug_probabilities((board, layout) in consistent_board(), bug_percentage in 0u8..=100) {
+            for bugs in [Bugs::Percentage(bug_percentage), Bugs::Total(layout.num_bugs() as u16)] {
+                let probabilities = board.bug_probabilities(bugs).unwrap();
+                for (field, probability) in board.fields.iter().zip(probabilities) {
+                    proptest::prop_assert_eq!(probability.is_some(), !is_open(field.data));
+                    if let Some(probability) = probability {
+                        proptest::prop_assert!(!probability.total.is_zero() && probability.bugs <= probability.total);
+                        if field.data == UNOPENED_BUGFREE {
+                            proptest::prop_assert!(probability.bugs.is_zero());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
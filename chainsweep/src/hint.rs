@@ -0,0 +1,177 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::iter::once;
+
+use crate::bignum::BigUint;
+use crate::field::{Bugs, GameData, Inconsistency, Probability, FLAGGED, UNOPENED, UNOPENED_BUGFREE};
+
+// An unopened field to open next, with what is known about it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    pub x: u8,
+    pub y: u8,
+    // The chance that the field is a bug
+    pub bug_chance: Probability,
+    // The chance that the field shows a 0, so the fields around it get opened as well. Only
+    // calculated with lookahead.
+    pub zero_chance: Option<Probability>,
+    // The player flagged the field as a suspected bug, so it can't be opened before removing the flag
+    pub flagged: bool,
+}
+
+// All unopened fields, best move first: the fields least likely to be a bug come first. With lookahead,
+// fields that are equally safe are ordered by how likely they are to open up more of the board, which
+// is what happens when a field shows a 0. A flagged field comes after unflagged fields that are just as
+// safe, but before riskier ones, so the best move can be to remove a flag.
+pub fn suggest_move(data: &GameData, bugs: Bugs, lookahead: bool) -> Result<Vec<Hint>, Inconsistency> {
+    let probabilities = data.bug_probabilities(bugs)?;
+    // Weight of all layouts, to compare the layouts where a field is a 0 with. Like bug_probabilities,
+    // a bug chance that allows none of the layouts makes them all equally likely.
+    let (total, uniform) = match (lookahead, bugs) {
+        (false, _) => (BigUint::zero(), false),
+        (true, Bugs::Percentage(_)) => {
+            let total = data.count_layouts_with(bugs)?;
+            if total.is_zero() {
+                (data.count_layouts()?, true)
+            } else {
+                (total, false)
+            }
+        }
+        (true, Bugs::Total(_)) => (data.count_layouts_with(bugs)?, false),
+    };
+    let mut hints = Vec::new();
+    for (index, probability) in probabilities.into_iter().enumerate() {
+        let Some(bug_chance) = probability else {
+            continue;
+        };
+        let (x, y) = ((index % data.width() as usize) as u8, (index / data.width() as usize) as u8);
+        let zero_chance = lookahead.then(|| zero_chance(data, bugs, &total, uniform, x, y));
+        hints.push(Hint { x, y, bug_chance, zero_chance, flagged: data.is_flagged(x, y) });
+    }
+    hints.sort_by(|a, b| {
+        compare(&a.bug_chance, &b.bug_chance).then_with(|| a.flagged.cmp(&b.flagged)).then_with(|| match (&a.zero_chance, &b.zero_chance) {
+            (Some(a), Some(b)) => compare(b, a),
+            _ => Ordering::Equal,
+        })
+    });
+    Ok(hints)
+}
+
+fn compare(a: &Probability, b: &Probability) -> Ordering {
+    a.bugs.mul(&b.total).cmp(&b.bugs.mul(&a.total))
+}
+
+// The chance that (x, y) and all unopened fields around it are bug-free, as the weight of the layouts
+// where that is the case over total, the weight of all layouts. When uniform is set every layout has
+// the same weight and the layouts are counted instead.
+fn zero_chance(data: &GameData, bugs: Bugs, total: &BigUint, uniform: bool, x: u8, y: u8) -> Probability {
+    let mut values = data.data();
+    let mut num_safe = 0;
+    for index in once((y as usize) * (data.width() as usize) + (x as usize)).chain(data.surrounding_fields(x, y)) {
+        if values[index] == UNOPENED || values[index] == FLAGGED {
+            values[index] = UNOPENED_BUGFREE;
+            num_safe += 1;
+        }
+    }
+    let safe = GameData::new(data.width(), data.height(), values);
+    let zero = if uniform { safe.count_layouts() } else { safe.count_layouts_with(bugs) };
+    let mut zero = zero.unwrap_or_else(|_| BigUint::zero());
+    if let (Bugs::Percentage(bug_percentage), false) = (bugs, uniform) {
+        // The fields that were decided are left out of the weight, add their chance of being bug-free
+        zero = zero.mul(&BigUint::from(100 - bug_percentage.min(100) as u64).pow(num_safe));
+    }
+    Probability {
+        bugs: zero,
+        total: total.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::BUG;
+
+    fn positions(hints: &[Hint]) -> Vec<(u8, u8)> {
+        hints.iter().map(|hint| (hint.x, hint.y)).collect()
+    }
+
+    #[test]
+    fn test_survival_first() {
+        let o = UNOPENED;
+        let data = GameData::new(4, 1, vec![0, o, 1, o]);
+        let hints = suggest_move(&data, Bugs::Percentage(20), false).unwrap();
+        assert_eq!(positions(&hints), vec![(1, 0), (3, 0)]);
+        assert!(hints[0].bug_chance.bugs.is_zero());
+        assert_eq!(hints[1].bug_chance.per_million(), 1_000_000);
+        assert_eq!(hints[0].zero_chance, None);
+    }
+
+    #[test]
+    fn test_flagged() {
+        let o = UNOPENED;
+        // The flagged field is the only safe one, so the player is better off removing the flag than
+        // opening a certain bug
+        let data = GameData::new(4, 1, vec![FLAGGED, 0, 1, o]);
+        for lookahead in [false, true] {
+            let hints = suggest_move(&data, Bugs::Percentage(20), lookahead).unwrap();
+            assert_eq!(positions(&hints), vec![(0, 0), (3, 0)]);
+            assert_eq!((hints[0].flagged, hints[1].flagged), (true, false));
+            assert!(hints[0].bug_chance.bugs.is_zero());
+            assert_eq!(hints[1].bug_chance.per_million(), 1_000_000);
+        }
+
+        // Between equally safe fields the unflagged one comes first
+        let data = GameData::new(5, 1, vec![FLAGGED, 0, o, 1, o]);
+        for lookahead in [false, true] {
+            let hints = suggest_move(&data, Bugs::Percentage(20), lookahead).unwrap();
+            assert_eq!(positions(&hints), vec![(2, 0), (0, 0), (4, 0)]);
+        }
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let o = UNOPENED;
+        // Nothing opened yet: every field is as likely to be a bug, but the corners have the fewest
+        // neighbours and are most likely to be a 0
+        let data = GameData::new(3, 3, vec![o; 9]);
+        let hints = suggest_move(&data, Bugs::Percentage(20), true).unwrap();
+        assert_eq!(positions(&hints[..4]), vec![(0, 0), (2, 0), (0, 2), (2, 2)]);
+        assert_eq!(positions(&hints[8..]), vec![(1, 1)]);
+        // 0.8^4 for a corner, 0.8^9 in the middle
+        assert_eq!(hints[0].zero_chance.as_ref().unwrap().per_million(), 409_600);
+        assert_eq!(hints[8].zero_chance.as_ref().unwrap().per_million(), 134_217);
+        let hints = suggest_move(&data, Bugs::Percentage(20), false).unwrap();
+        assert_eq!(positions(&hints[..2]), vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_zero_chance() {
+        let o = UNOPENED;
+        let data = GameData::new(4, 3, vec![
+            o, o, o, o,
+            1, 2, o, o,
+            0, 1, o, o,
+        ]);
+        for bugs in [Bugs::Total(3), Bugs::Percentage(0), Bugs::Percentage(30)] {
+            let hints = suggest_move(&data, bugs, true).unwrap();
+            for hint in hints {
+                // Check against the layouts where the field is a 0
+                let is_zero = |layout: &GameData| layout.get(hint.x, hint.y).data != BUG && layout.get(hint.x, hint.y).adjacent_bugs == 0;
+                let weigh = |layout: &GameData| match bugs {
+                    Bugs::Total(total) => BigUint::from((layout.data().iter().filter(|&&value| value == BUG).count() == total as usize) as u64),
+                    Bugs::Percentage(0) => BigUint::from(1),
+                    Bugs::Percentage(bug_percentage) => {
+                        let num_bugs = layout.data().iter().filter(|&&value| value == BUG).count();
+                        BigUint::from(bug_percentage as u64).pow(num_bugs).mul(&BigUint::from(100 - bug_percentage as u64).pow(8 - num_bugs))
+                    }
+                };
+                let (zero, total) = data.layouts().fold((BigUint::zero(), BigUint::zero()), |(zero, total), layout| {
+                    let weight = weigh(&layout);
+                    (if is_zero(&layout) { zero.add(&weight) } else { zero }, total.add(&weight))
+                });
+                let zero_chance = hint.zero_chance.unwrap();
+                assert_eq!(zero_chance.bugs.mul(&total), zero.mul(&zero_chance.total));
+            }
+        }
+    }
+}
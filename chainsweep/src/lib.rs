@@ -2,7 +2,12 @@
 #![cfg_attr(not(any(feature = "export-abi", test)), no_std)]
 
 mod sweeper;
-mod field;
+pub mod field;
+pub mod bignum;
+pub mod deduce;
+pub mod hint;
+pub mod packed;
+pub mod rules;
 
 extern crate alloc;
 
@@ -11,11 +16,11 @@ extern crate alloc;
 static ALLOC: mini_alloc::MiniAlloc = mini_alloc::MiniAlloc::INIT;
 
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 
 use alloy_primitives::Address;
-use stylus_sdk::{block, evm, msg, prelude::*};
-use sweeper::{GameAlreadyStarted, GameError, GameStarted, Game};
+use stylus_sdk::{block, msg, prelude::*};
+use sweeper::{GameAlreadyStarted, GameError, Game, MoveHistory, DEFAULT_HEIGHT, DEFAULT_WIDTH, DIFFICULTY_INTERMEDIATE, MODE_NORMAL};
 
 sol_storage! {
     #[entrypoint]
@@ -26,14 +31,33 @@ sol_storage! {
 
 #[external]
 impl SweeperGame {
+    // A normal game of DEFAULT_WIDTH x DEFAULT_HEIGHT on intermediate difficulty
     pub fn new_game(&mut self) -> Result<String, GameError> {
+        self.new_game_with_mode(MODE_NORMAL, DEFAULT_WIDTH, DEFAULT_HEIGHT, DIFFICULTY_INTERMEDIATE, 0)
+    }
+
+    // Overload of newGame for a board of width x height, between MIN_SIZE and MAX_WIDTH x MAX_HEIGHT
+    #[selector(name = "newGame")]
+    pub fn new_game_with_size(&mut self, width: u8, height: u8) -> Result<String, GameError> {
+        self.new_game_with_mode(MODE_NORMAL, width, height, DIFFICULTY_INTERMEDIATE, 0)
+    }
+
+    // Overload of newGame with a difficulty: 0 (beginner), 1 (intermediate) and 2 (expert) are presets,
+    // with 3 bugs is the chance of every field being a bug as a percentage, with 4 the number of bugs
+    #[selector(name = "newGame")]
+    pub fn new_game_with_difficulty(&mut self, width: u8, height: u8, difficulty: u8, bugs: u16) -> Result<String, GameError> {
+        self.new_game_with_mode(MODE_NORMAL, width, height, difficulty, bugs)
+    }
+
+    // mode 0 is a normal game, in mode 1 (evil) every guess that isn't forced to be safe is a bug,
+    // in mode 2 (forgiving) a guess is safe when there was no provably safe field to open
+    pub fn new_game_with_mode(&mut self, mode: u8, width: u8, height: u8, difficulty: u8, bugs: u16) -> Result<String, GameError> {
         let caller = msg::sender();
         let mut game = self.games.setter(caller);
         if game.is_started() && !game.is_ended() {
             return Err(GameError::GameAlreadyStarted(GameAlreadyStarted {}));
         }
-        game.init();
-        evm::log(GameStarted { player: caller });
+        game.init(mode, width, height, difficulty, bugs)?;
 
         Ok(game.print())
     }
@@ -44,7 +68,47 @@ impl SweeperGame {
 
     pub fn view_completed(&self, address: Address, seed: u64) -> Result<String, GameError> {
         let game = self.games.get(address);
-        Ok(game.print_filled_in(seed))
+        game.print_filled_in(seed)
+    }
+
+    // Best field to open next for the player, as (x, y, chance of a bug in parts per million).
+    // With lookahead, equally safe fields are ranked by their chance to open up more of the board. The
+    // field can be one the player flagged, when that is safer than every unflagged field.
+    pub fn suggest_move(&self, address: Address, lookahead: bool) -> Result<(u8, u8, u32), GameError> {
+        self.games.get(address).suggest_move(lookahead)
+    }
+
+    // Mark an unopened field as a suspected bug or remove the mark, flagged fields can't be opened.
+    // Returns whether the field is flagged now.
+    pub fn toggle_flag(&mut self, x: u8, y: u8) -> Result<bool, GameError> {
+        let caller = msg::sender();
+        let mut game = self.games.setter(caller);
+        game.toggle_flag(x, y)
+    }
+
+    // Bug counter for games with a fixed number of bugs (difficulty 4): the number of bugs minus the
+    // opened bugs and the flags. Reverts for games with a bug chance.
+    pub fn remaining_bugs(&self, address: Address) -> Result<u16, GameError> {
+        self.games.get(address).remaining_bugs()
+    }
+
+    // Number of guesses the player made in the current or last game
+    pub fn move_count(&self, address: Address) -> Result<u32, GameError> {
+        Ok(self.games.get(address).move_count())
+    }
+
+    // All guesses of the player in the current or last game, oldest first, as the guessed fields, the
+    // value that was revealed and the random seed that was used to fill in the board
+    pub fn move_history(&self, address: Address) -> Result<MoveHistory, GameError> {
+        Ok(self.games.get(address).moves())
+    }
+
+    // Give up the current game, which counts as lost, so a new one can be started. This is the way out
+    // when a guess keeps reverting with BoardTooComplex.
+    pub fn abandon_game(&mut self) -> Result<(), GameError> {
+        let caller = msg::sender();
+        let mut game = self.games.setter(caller);
+        game.abandon()
     }
 
     pub fn make_guess(&mut self, x: u8, y: u8) -> Result<u8, GameError> {
@@ -52,5 +116,14 @@ impl SweeperGame {
         let mut game = self.games.setter(caller);
         game.make_guess(x, y, block::timestamp() ^ block::gas_limit())
     }
+
+    // Open several fields in one transaction, in order, stopping when the game is over. Returns the
+    // value of every guessed field, see make_guess. Fields opened by an earlier guess of the same call
+    // are fine to list, any other invalid guess reverts the whole call.
+    pub fn make_guesses(&mut self, xs: Vec<u8>, ys: Vec<u8>) -> Result<Vec<u8>, GameError> {
+        let caller = msg::sender();
+        let mut game = self.games.setter(caller);
+        game.make_guesses(&xs, &ys, block::timestamp() ^ block::gas_limit())
+    }
 }
 
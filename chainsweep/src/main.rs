@@ -1,8 +1,276 @@
-#![cfg_attr(not(any(feature = "export-abi", test)), no_main)]
-#![cfg_attr(not(any(feature = "export-abi", test)), no_std)]
+#![cfg_attr(all(target_arch = "wasm32", not(feature = "export-abi")), no_main)]
+#![cfg_attr(all(target_arch = "wasm32", not(feature = "export-abi")), no_std)]
 
 #[cfg(feature = "export-abi")]
 fn main() {
   chainsweep::main();
 }
 
+// Play a game in the terminal, with the same rules as the contract. Every guess gets its own seed from
+// the game seed, so a game can be replayed exactly by passing the same seed and making the same guesses.
+#[cfg(not(any(feature = "export-abi", target_arch = "wasm32")))]
+fn main() {
+    use std::io::{self, BufRead, Write};
+
+    if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", terminal::USAGE);
+        return;
+    }
+    let settings = match terminal::Settings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, terminal::USAGE);
+            std::process::exit(2);
+        }
+    };
+    println!("seed {}, {}x{} with {}, {} mode", settings.seed, settings.width, settings.height, settings.bugs_description(), settings.mode_name());
+    println!("Enter \"x y\" to open a field, \"hint\" for a suggestion, \"quit\" to stop");
+    let mut game = terminal::Game::new(&settings);
+    print!("{}", game.board());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    while game.state() == terminal::State::Playing {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => continue,
+            ["quit"] | ["q"] => break,
+            ["hint"] | ["h"] => match game.hint() {
+                Ok(hint) => println!("{}", hint),
+                Err(inconsistency) => println!("inconsistent board: {:?}", inconsistency),
+            },
+            [x, y] => match (x.parse(), y.parse()) {
+                (Ok(x), Ok(y)) => match game.guess(x, y) {
+                    Ok(()) => print!("{}", game.board()),
+                    Err(message) => println!("{}", message),
+                },
+                _ => println!("x and y have to be numbers"),
+            },
+            _ => println!("unknown command"),
+        }
+    }
+    match game.state() {
+        terminal::State::Won => println!("Won"),
+        terminal::State::Lost => println!("Lost"),
+        terminal::State::Playing => println!("Stopped, seed was {}", settings.seed),
+    }
+}
+
+#[cfg(not(any(feature = "export-abi", target_arch = "wasm32")))]
+mod terminal {
+    use std::fmt;
+
+    use chainsweep::field::{Bugs, GameData, Inconsistency, UNOPENED};
+    use chainsweep::hint::suggest_move;
+    use chainsweep::rules::{make_guess, Event, Mode, MoveError};
+    use fastrand::Rng;
+
+    pub const USAGE: &str = "usage: chainsweep [--seed N] [--width N] [--height N] [--bugs PERCENTAGE | --total N] [--mode normal|evil|forgiving]";
+
+    pub struct Settings {
+        pub seed: u64,
+        pub width: u8,
+        pub height: u8,
+        pub bugs: Bugs,
+        pub mode: Mode,
+    }
+
+    impl Settings {
+        // Defaults are the same as the contract: 5x5 with a 20% bug chance
+        pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+            let mut settings = Settings {
+                seed: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|duration| duration.as_nanos() as u64)
+                    .unwrap_or(0),
+                width: 5,
+                height: 5,
+                bugs: Bugs::Percentage(20),
+                mode: Mode::Normal,
+            };
+            while let Some(arg) = args.next() {
+                let value = args.next().ok_or(format!("missing value for {}", arg))?;
+                let number = |value: &str| value.parse::<u64>().map_err(|_| format!("invalid value for {}: {}", arg, value));
+                match arg.as_str() {
+                    "--seed" => settings.seed = number(&value)?,
+                    "--width" => settings.width = number(&value)?.clamp(1, 255) as u8,
+                    "--height" => settings.height = number(&value)?.clamp(1, 255) as u8,
+                    "--bugs" => settings.bugs = Bugs::Percentage(number(&value)?.min(100) as u8),
+                    "--total" => settings.bugs = Bugs::Total(number(&value)?.min(u16::MAX as u64) as u16),
+                    "--mode" => {
+                        settings.mode = match value.as_str() {
+                            "normal" => Mode::Normal,
+                            "evil" => Mode::Evil,
+                            "forgiving" => Mode::Forgiving,
+                            _ => return Err(format!("unknown mode {}", value)),
+                        }
+                    }
+                    _ => return Err(format!("unknown option {}", arg)),
+                }
+            }
+            Ok(settings)
+        }
+
+        pub fn mode_name(&self) -> &'static str {
+            match self.mode {
+                Mode::Normal => "normal",
+                Mode::Evil => "evil",
+                Mode::Forgiving => "forgiving",
+            }
+        }
+
+        pub fn bugs_description(&self) -> String {
+            match self.bugs {
+                Bugs::Percentage(bug_percentage) => format!("{}% bugs", bug_percentage),
+                Bugs::Total(total) => format!("{} bugs", total),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum State {
+        Playing,
+        Lost,
+        Won,
+    }
+
+    // The same as the contract stores: the opened fields, and the bug that was hit when the game is lost
+    pub struct Game {
+        width: u8,
+        height: u8,
+        fields: Vec<u8>,
+        bugs: Bugs,
+        mode: Mode,
+        rng: Rng,
+        state: State,
+    }
+
+    impl Game {
+        pub fn new(settings: &Settings) -> Self {
+            Game {
+                width: settings.width,
+                height: settings.height,
+                fields: vec![UNOPENED; settings.width as usize * settings.height as usize],
+                bugs: settings.bugs,
+                mode: settings.mode,
+                rng: Rng::with_seed(settings.seed),
+                state: State::Playing,
+            }
+        }
+
+        pub fn state(&self) -> State {
+            self.state
+        }
+
+        pub fn board(&self) -> GameData {
+            GameData::new(self.width, self.height, self.fields.clone())
+        }
+
+        // Open a field, with the same rules as Game::make_guess of the contract
+        pub fn guess(&mut self, x: u8, y: u8) -> Result<(), String> {
+            // Only use up a seed for valid guesses, so replaying a game doesn't depend on typos
+            let mut rng = self.rng.clone();
+            let events = make_guess(&self.board(), x, y, rng.u64(..), self.bugs, self.mode).map_err(|error| match error {
+                MoveError::OutsideBoard => format!("({}, {}) is not on the board", x, y),
+                MoveError::FieldAlreadyOpened => format!("({}, {}) is already opened", x, y),
+                MoveError::Flagged => format!("({}, {}) is flagged", x, y),
+                MoveError::Inconsistent(Inconsistency::TooComplex) => "the board is too complex to solve, try another field or quit".to_string(),
+                MoveError::Inconsistent(inconsistency) => format!("inconsistent board: {:?}", inconsistency),
+            })?;
+            self.rng = rng;
+            for event in events {
+                match event {
+                    Event::FieldOpened { x, y, value, .. } => self.fields[y as usize * self.width as usize + x as usize] = value,
+                    Event::GameOver { won } => self.state = if won { State::Won } else { State::Lost },
+                }
+            }
+            Ok(())
+        }
+
+        pub fn hint(&self) -> Result<Hint, Inconsistency> {
+            let hints = suggest_move(&self.board(), self.bugs, true)?;
+            let best = &hints[0];
+            Ok(Hint {
+                x: best.x,
+                y: best.y,
+                bug_chance: best.bug_chance.per_million(),
+            })
+        }
+    }
+
+    pub struct Hint {
+        x: u8,
+        y: u8,
+        // Parts per million
+        bug_chance: u32,
+    }
+
+    impl fmt::Display for Hint {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "open {} {}, chance of a bug {}.{:04}%", self.x, self.y, self.bug_chance / 10_000, self.bug_chance % 10_000)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chainsweep::field::{is_open, BUG};
+
+        fn settings(args: &[&str]) -> Settings {
+            Settings::from_args(args.iter().map(|arg| arg.to_string())).unwrap()
+        }
+
+        #[test]
+        fn test_first_guess_safe() {
+            for seed in 0..50 {
+                let mut game = Game::new(&settings(&["--seed", &seed.to_string(), "--bugs", "90"]));
+                game.guess(2, 2).unwrap();
+                assert_ne!(game.state(), State::Lost);
+                assert!(is_open(game.board().get(2, 2).data));
+            }
+        }
+
+        #[test]
+        fn test_flood_fill_and_win() {
+            // Without bugs the first guess opens everything
+            let mut game = Game::new(&settings(&["--seed", "1", "--bugs", "0", "--width", "8"]));
+            game.guess(0, 0).unwrap();
+            assert_eq!(game.board().data(), vec![0; 40]);
+            assert_eq!(game.state(), State::Won);
+            assert!(game.guess(1, 1).is_err());
+        }
+
+        #[test]
+        fn test_replay() {
+            let play = || {
+                let mut game = Game::new(&settings(&["--seed", "42", "--mode", "evil", "--total", "5"]));
+                let mut guesses = 0;
+                while game.state() == State::Playing {
+                    let hint = game.hint().unwrap();
+                    game.guess(hint.x, hint.y).unwrap();
+                    guesses += 1;
+                }
+                (game.board().data(), game.state(), guesses)
+            };
+            let (board, state, guesses) = play();
+            assert_eq!(play(), (board.clone(), state, guesses));
+            // In evil mode only the first guess and provably safe guesses survive
+            assert!(guesses > 1);
+            assert!(board.iter().filter(|&&value| value == BUG).count() <= 1);
+        }
+
+        #[test]
+        fn test_settings() {
+            assert!(Settings::from_args(["--mode".to_string(), "easy".to_string()].into_iter()).is_err());
+            assert!(Settings::from_args(["--seed".to_string()].into_iter()).is_err());
+            let settings = settings(&["--total", "3", "--height", "300"]);
+            assert_eq!(settings.bugs, Bugs::Total(3));
+            assert_eq!(settings.height, 255);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use alloc::vec::Vec;
+
+use crate::field::UNOPENED;
+
+// The fields of a board are stored with 4 bits each, row by row, in 256-bit words. A word is handled
+// as its 32 bytes in little endian order, field i of a word is in the low 4 bits of byte i / 2 for
+// even i and in the high 4 bits for odd i.
+pub const FIELDS_PER_WORD: usize = 64;
+
+pub type Word = [u8; 32];
+
+// Where the packed words are kept: in contract storage, or in memory for tests and tools
+pub trait Words {
+    fn get(&self, index: usize) -> Word;
+    // Overwrite the word at index, or add it when index is one past the last word
+    fn set(&mut self, index: usize, word: Word);
+    fn truncate(&mut self, len: usize);
+}
+
+impl Words for Vec<Word> {
+    fn get(&self, index: usize) -> Word {
+        self[index]
+    }
+
+    fn set(&mut self, index: usize, word: Word) {
+        if index == Vec::len(self) {
+            self.push(word);
+        } else {
+            self[index] = word;
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+}
+
+pub fn num_words(num_fields: usize) -> usize {
+    num_fields.div_ceil(FIELDS_PER_WORD)
+}
+
+// Start a board of num_fields fields that are all UNOPENED. Words left over from a bigger board are
+// dropped.
+pub fn reset(words: &mut impl Words, num_fields: usize) {
+    let unopened = [UNOPENED << 4 | UNOPENED; 32];
+    let num_words = num_words(num_fields);
+    for index in 0..num_words {
+        words.set(index, unopened);
+    }
+    words.truncate(num_words);
+}
+
+pub fn set_field(words: &mut impl Words, field: usize, value: u8) {
+    let index = field / FIELDS_PER_WORD;
+    let mut word = words.get(index);
+    let bit_offset = (field % FIELDS_PER_WORD) * 4;
+    let byte_index = bit_offset / 8;
+    let bit_shift = bit_offset % 8;
+    let mask = 0xFu8 << bit_shift;
+    word[byte_index] = (word[byte_index] & !mask) | ((value & 0xF) << bit_shift);
+    words.set(index, word);
+}
+
+// The first num_fields fields, as passed to GameData::new
+pub fn fields(words: &impl Words, num_fields: usize) -> Vec<u8> {
+    let mut fields = Vec::with_capacity(num_fields);
+    for index in 0..num_words(num_fields) {
+        let word = words.get(index);
+        for field in 0..FIELDS_PER_WORD.min(num_fields - fields.len()) {
+            let byte = word[field / 2];
+            fields.push(if field % 2 == 1 { byte >> 4 } else { byte & 0xF });
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{BUG, FLAGGED};
+
+    // Every value a field can be stored with, in a pattern that doesn't repeat at word boundaries
+    fn values(num_fields: usize) -> Vec<u8> {
+        (0..num_fields).map(|field| [0, 3, 8, BUG, UNOPENED, FLAGGED, 1][field % 7]).collect()
+    }
+
+    fn store(num_fields: usize) -> Vec<Word> {
+        let mut words = Vec::new();
+        reset(&mut words, num_fields);
+        for (field, value) in values(num_fields).into_iter().enumerate() {
+            set_field(&mut words, field, value);
+        }
+        words
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        // 9x8 needs a second word for the last 8 fields, 16x16 exactly fills 4 words
+        for (num_fields, expected_words) in [(25, 1), (64, 1), (72, 2), (256, 4)] {
+            let words = store(num_fields);
+            assert_eq!(words.len(), expected_words);
+            assert_eq!(fields(&words, num_fields), values(num_fields));
+        }
+        let words = store(72);
+        // Field 64 is the low half of the first byte of the second word, field 71 the high half of byte 3
+        assert_eq!(words[1][0] & 0xF, values(72)[64]);
+        assert_eq!(words[1][3] >> 4, values(72)[71]);
+        // The rest of the partial word is still UNOPENED
+        assert!(words[1][4..].iter().all(|&byte| byte == UNOPENED << 4 | UNOPENED));
+    }
+
+    #[test]
+    fn test_set_field() {
+        let mut words = Vec::new();
+        reset(&mut words, 256);
+        assert_eq!(fields(&words, 256), vec![UNOPENED; 256]);
+        // Neighbours in the same byte and across a word boundary keep their values
+        for field in [62, 63, 64, 65, 255] {
+            set_field(&mut words, field, field as u8 % 9);
+        }
+        set_field(&mut words, 63, FLAGGED);
+        let fields = fields(&words, 256);
+        assert_eq!(&fields[61..67], &[UNOPENED, 62 % 9, FLAGGED, 64 % 9, 65 % 9, UNOPENED]);
+        assert_eq!(fields[255], 255 % 9);
+    }
+
+    #[test]
+    fn test_reset_smaller() {
+        // A 5x5 game after a 16x16 one starts out unopened and only keeps the words it needs
+        let mut words = store(256);
+        reset(&mut words, 25);
+        assert_eq!(words.len(), 1);
+        assert_eq!(fields(&words, 25), vec![UNOPENED; 25]);
+        // And a bigger one after that doesn't see the old values either
+        reset(&mut words, 72);
+        assert_eq!(words.len(), 2);
+        assert_eq!(fields(&words, 72), vec![UNOPENED; 72]);
+    }
+}
@@ -0,0 +1,170 @@
+use alloc::{vec, vec::Vec};
+
+use crate::field::{is_open, Bugs, GameData, Inconsistency, BUG, UNOPENED_BUGFREE};
+
+// How the board is filled in on every guess
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // Bugs are placed at random, the guessed field is decided with a single roll against its exact chance
+    Normal,
+    // A guessed field is a bug whenever that is consistent with the board
+    Evil,
+    // A guessed field is bug-free if the player had no provably safe field to open instead
+    Forgiving,
+}
+
+// What a guess did to the game, in the order it happened
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    // The field (x, y) now shows value: the number of bugs around it, or BUG if the player hit one.
+    // bug_chance is the chance of a bug that was applied when the field was opened, in parts per
+    // million. In evil and forgiving mode that can be certain either way, whatever the odds were.
+    FieldOpened { x: u8, y: u8, value: u8, bug_chance: u32 },
+    GameOver { won: bool },
+}
+
+// Why a guess can't be made
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    OutsideBoard,
+    FieldAlreadyOpened,
+    // The player flagged the field as a suspected bug
+    Flagged,
+    Inconsistent(Inconsistency),
+}
+
+impl From<Inconsistency> for MoveError {
+    fn from(inconsistency: Inconsistency) -> Self {
+        MoveError::Inconsistent(inconsistency)
+    }
+}
+
+// Open the field (x, y) on a board that only has the fields the player opened so far. The board is
+// filled in according to the mode, and the player either hits a bug and loses, or the field is opened
+// together with everything around fields that have no bugs around them. The player wins when only
+// bugs are left unopened.
+// The very first guess of a game is never a bug.
+pub fn make_guess(board: &GameData, x: u8, y: u8, rand_seed: u64, bugs: Bugs, mode: Mode) -> Result<Vec<Event>, MoveError> {
+    if x >= board.width() || y >= board.height() {
+        return Err(MoveError::OutsideBoard);
+    }
+    if is_open(board.get(x, y).data) {
+        return Err(MoveError::FieldAlreadyOpened);
+    }
+    if board.is_flagged(x, y) {
+        return Err(MoveError::Flagged);
+    }
+    let mut field_data = board.clone();
+    if field_data.num_open == 0 {
+        field_data.set_data(x, y, UNOPENED_BUGFREE);
+    }
+    let (mut filled_in, bug_chance) = match mode {
+        Mode::Evil => field_data.fill_in_evil(rand_seed, bugs, x, y)?,
+        Mode::Forgiving => field_data.fill_in_forgiving(rand_seed, bugs, x, y)?,
+        Mode::Normal => field_data.reveal(rand_seed, bugs, x, y)?,
+    };
+    let bug_chance = bug_chance.per_million();
+
+    if filled_in.get(x, y).data == BUG {
+        return Ok(vec![
+            Event::FieldOpened { x, y, value: BUG, bug_chance },
+            Event::GameOver { won: false },
+        ]);
+    }
+
+    let mut events = Vec::new();
+    // Fields still to open, the last one first. Fields next to a 0 are never a bug.
+    let mut to_open = vec![(x, y, bug_chance)];
+    while let Some((x, y, bug_chance)) = to_open.pop() {
+        if is_open(filled_in.get(x, y).data) {
+            continue;
+        }
+        let count = filled_in.get(x, y).adjacent_bugs;
+        filled_in.set_data(x, y, count);
+        events.push(Event::FieldOpened { x, y, value: count, bug_chance });
+        if count == 0 {
+            let width = filled_in.width() as usize;
+            for other in filled_in.surrounding_fields(x, y).into_iter().rev() {
+                to_open.push(((other % width) as u8, (other / width) as u8, 0));
+            }
+        }
+    }
+    if filled_in.data().iter().all(|&value| value != UNOPENED_BUGFREE) {
+        events.push(Event::GameOver { won: true });
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{FLAGGED, UNOPENED};
+
+    fn opened(events: &[Event]) -> Vec<(u8, u8, u8)> {
+        events
+            .iter()
+            .filter_map(|event| match *event {
+                Event::FieldOpened { x, y, value, .. } => Some((x, y, value)),
+                Event::GameOver { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_first_guess() {
+        let board = GameData::new(5, 5, vec![UNOPENED; 25]);
+        for seed in 0..50 {
+            for mode in [Mode::Normal, Mode::Evil, Mode::Forgiving] {
+                let events = make_guess(&board, 1, 3, seed, Bugs::Percentage(90), mode).unwrap();
+                let Event::FieldOpened { x: 1, y: 3, value, .. } = events[0] else {
+                    panic!("unexpected first event {:?}", events[0]);
+                };
+                assert_ne!(value, BUG);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let o = UNOPENED;
+        // Only the bottom right corner can hold the bug
+        let board = GameData::new(4, 3, vec![
+            o, o, o, o,
+            o, o, 1, 1,
+            o, o, 1, o,
+        ]);
+        let events = make_guess(&board, 0, 0, 0, Bugs::Total(1), Mode::Normal).unwrap();
+        // Every field is opened once, depth first in the order of the fields around each 0
+        assert_eq!(opened(&events), vec![
+            (0, 0, 0), (0, 1, 0), (0, 2, 0), (1, 1, 0), (1, 0, 0), (2, 0, 0), (3, 0, 0), (1, 2, 0),
+        ]);
+        assert_eq!(events.last(), Some(&Event::GameOver { won: true }));
+        assert_eq!(events[0], Event::FieldOpened { x: 0, y: 0, value: 0, bug_chance: 0 });
+    }
+
+    #[test]
+    fn test_lose() {
+        let o = UNOPENED;
+        let board = GameData::new(3, 1, vec![o, 1, o]);
+        let events = make_guess(&board, 0, 0, 0, Bugs::Percentage(20), Mode::Evil).unwrap();
+        assert_eq!(events, vec![
+            Event::FieldOpened { x: 0, y: 0, value: BUG, bug_chance: 1_000_000 },
+            Event::GameOver { won: false },
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_guesses() {
+        let o = UNOPENED;
+        let board = GameData::new(3, 1, vec![o, 1, o]);
+        assert_eq!(make_guess(&board, 1, 0, 0, Bugs::Percentage(20), Mode::Normal), Err(MoveError::FieldAlreadyOpened));
+        assert_eq!(make_guess(&board, 3, 0, 0, Bugs::Percentage(20), Mode::Normal), Err(MoveError::OutsideBoard));
+        let board = GameData::new(3, 1, vec![FLAGGED, 1, o]);
+        assert_eq!(make_guess(&board, 0, 0, 0, Bugs::Percentage(20), Mode::Normal), Err(MoveError::Flagged));
+        let board = GameData::new(3, 1, vec![o, 0, 1]);
+        assert_eq!(
+            make_guess(&board, 0, 0, 0, Bugs::Percentage(20), Mode::Normal),
+            Err(MoveError::Inconsistent(Inconsistency::Fields(vec![(2, 0)])))
+        );
+    }
+}
@@ -3,7 +3,7 @@ use alloy_sol_types::sol;
 use stylus_sdk::{
     console, evm, msg,
     prelude::*,
-    storage::{StorageU256, StorageU8},
+    storage::{StorageU16, StorageU256, StorageU64, StorageU8, StorageVec},
 };
 
 use alloc::{
@@ -11,29 +11,59 @@ use alloc::{
     vec::Vec,
 };
 
-use crate::field::{is_open, GameData, BUG, UNOPENED, UNOPENED_BUGFREE};
+use crate::field::{is_open, to_char, Bugs, GameData, Inconsistency, InvalidBoard, BUG, FLAGGED, UNOPENED, UNOPENED_BUGFREE};
+use crate::hint::suggest_move;
+use crate::packed::{self, Word, Words};
+use crate::rules::{self, Event, Mode, MoveError};
 
-// Field size is fixed
-// This will fit in one u256 (4 bits * 8 * 8 = 256 bits)
-// const WIDTH: u8 = 8;
-// const HEIGHT: u8 = 8;
+// Every player picks the field size when starting a game, within these bounds.
+// The fields are stored with 4 bits each, so 64 fields fit in one u256. The gas of a guess on a big
+// board is bounded by field::MAX_SOLVE_STEPS instead of the size, see Game::abandon.
+pub const MIN_SIZE: u8 = 2;
+pub const MAX_WIDTH: u8 = 16;
+pub const MAX_HEIGHT: u8 = 16;
 
 // The current setup with a metamask confirmation for every field
-// is not very user friendly, so we'll keep the field size small
-const WIDTH: u8 = 5;
-const HEIGHT: u8 = 5;
+// is not very user friendly, so we'll keep the default field size small
+pub const DEFAULT_WIDTH: u8 = 5;
+pub const DEFAULT_HEIGHT: u8 = 5;
 
-// Chance of every field being a bug, as a percentage
-const BUG_CHANCE_100: u8 = 20;
+// How many bugs there are, chosen when starting a game. The presets are a chance of every field
+// being a bug, as a percentage, the custom difficulties take that percentage or the exact number of
+// bugs from the player.
+type Difficulty = u8;
+pub const DIFFICULTY_BEGINNER: Difficulty = 0;
+pub const DIFFICULTY_INTERMEDIATE: Difficulty = 1;
+pub const DIFFICULTY_EXPERT: Difficulty = 2;
+pub const DIFFICULTY_PERCENTAGE: Difficulty = 3;
+pub const DIFFICULTY_TOTAL: Difficulty = 4;
+const BUG_CHANCE_BEGINNER: u16 = 10;
+const BUG_CHANCE_INTERMEDIATE: u16 = 20;
+const BUG_CHANCE_EXPERT: u16 = 30;
+// Beyond this almost every guess is a bug
+const MAX_BUG_CHANCE: u16 = 90;
 
 sol! {
-    event GameStarted(address indexed player);
-    event FieldOpened(address indexed player, uint8 x, uint8 y, uint8 value);
+    // bugs: the bug chance as a percentage, or the number of bugs with DIFFICULTY_TOTAL
+    event GameStarted(address indexed player, uint8 mode, uint8 width, uint8 height, uint8 difficulty, uint16 bugs);
+    // bug_chance: the chance of a bug that was applied when the field was opened, in parts per million,
+    // see rules::Event
+    event FieldOpened(address indexed player, uint8 x, uint8 y, uint8 value, uint32 bug_chance);
     event GameOver(address indexed player, bool won);
+    event FieldFlagged(address indexed player, uint8 x, uint8 y, bool flagged);
 
     error GameAlreadyOver();
     error GameAlreadyStarted();
     error FieldAlreadyOpened();
+    error FieldOutsideBoard();
+    error FieldIsFlagged();
+    error UnknownMode(uint8 mode);
+    error InvalidBoardSize(uint8 width, uint8 height);
+    error InvalidDifficulty(uint8 difficulty, uint16 bugs);
+    error InconsistentBoard(uint8[] xs, uint8[] ys);
+    error MismatchedGuesses(uint256 xs, uint256 ys);
+    error NoBugTotal();
+    error BoardTooComplex();
 }
 
 #[derive(SolidityError)]
@@ -41,6 +71,55 @@ pub enum GameError {
     GameAlreadyOver(GameAlreadyOver),
     GameAlreadyStarted(GameAlreadyStarted),
     FieldAlreadyOpened(FieldAlreadyOpened),
+    FieldOutsideBoard(FieldOutsideBoard),
+    FieldIsFlagged(FieldIsFlagged),
+    UnknownMode(UnknownMode),
+    InvalidBoardSize(InvalidBoardSize),
+    InvalidDifficulty(InvalidDifficulty),
+    InconsistentBoard(InconsistentBoard),
+    MismatchedGuesses(MismatchedGuesses),
+    NoBugTotal(NoBugTotal),
+    BoardTooComplex(BoardTooComplex),
+}
+
+impl GameError {
+    fn inconsistent_board(fields: Vec<(u8, u8)>) -> Self {
+        GameError::InconsistentBoard(InconsistentBoard {
+            xs: fields.iter().map(|&(x, _)| x).collect(),
+            ys: fields.iter().map(|&(_, y)| y).collect(),
+        })
+    }
+}
+
+impl From<Inconsistency> for GameError {
+    fn from(inconsistency: Inconsistency) -> Self {
+        // No specific fields to blame if only the total number of bugs doesn't fit
+        match inconsistency {
+            Inconsistency::Fields(fields) => GameError::inconsistent_board(fields),
+            Inconsistency::TotalBugs => GameError::inconsistent_board(Vec::new()),
+            Inconsistency::TooComplex => GameError::BoardTooComplex(BoardTooComplex {}),
+        }
+    }
+}
+
+impl From<MoveError> for GameError {
+    fn from(error: MoveError) -> Self {
+        match error {
+            MoveError::OutsideBoard => GameError::FieldOutsideBoard(FieldOutsideBoard {}),
+            MoveError::FieldAlreadyOpened => GameError::FieldAlreadyOpened(FieldAlreadyOpened {}),
+            MoveError::Flagged => GameError::FieldIsFlagged(FieldIsFlagged {}),
+            MoveError::Inconsistent(inconsistency) => inconsistency.into(),
+        }
+    }
+}
+
+impl From<InvalidBoard> for GameError {
+    fn from(invalid: InvalidBoard) -> Self {
+        match invalid {
+            InvalidBoard::Values(fields) => GameError::inconsistent_board(fields),
+            InvalidBoard::Inconsistent(inconsistency) => inconsistency.into(),
+        }
+    }
 }
 
 // enum not supported in stylus yet? https://github.com/OffchainLabs/stylus-sdk-rs/issues/54
@@ -57,11 +136,57 @@ const STATE_PLAYING: GameState = 1;
 const STATE_LOST: GameState = 2;
 const STATE_WON: GameState = 3;
 
+// How the board is filled in on every guess, see rules::Mode
+type GameMode = u8;
+pub const MODE_NORMAL: GameMode = 0;
+pub const MODE_EVIL: GameMode = 1;
+pub const MODE_FORGIVING: GameMode = 2;
+
 #[solidity_storage]
 pub struct Game {
-    board_encoded: StorageU256,
+    // packed::FIELDS_PER_WORD fields per word, row by row
+    board_encoded: StorageVec<StorageU256>,
     state: StorageU8,
+    mode: StorageU8,
+    width: StorageU8,
+    height: StorageU8,
+    difficulty: StorageU8,
+    // The bug chance of the preset or the custom difficulty, see Game::bugs
+    bugs: StorageU16,
+    // Every guess of the game, in order
+    moves: StorageVec<Move>,
 }
+
+// A guess and what it revealed. Replaying rules::make_guess with the board before the guess and
+// rand_seed gives the same result.
+#[solidity_storage]
+pub struct Move {
+    x: StorageU8,
+    y: StorageU8,
+    value: StorageU8,
+    rand_seed: StorageU64,
+}
+
+// The moves of a game as (xs, ys, values, rand_seeds)
+pub type MoveHistory = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u64>);
+impl Words for StorageVec<StorageU256> {
+    fn get(&self, index: usize) -> Word {
+        StorageVec::get(self, index).unwrap().to_le_bytes()
+    }
+
+    fn set(&mut self, index: usize, word: Word) {
+        let word = U256::from_le_bytes(word);
+        match self.setter(index) {
+            Some(mut current) => current.set(word),
+            None => self.push(word),
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        StorageVec::truncate(self, len)
+    }
+}
+
 /*
 TODO:
   ✅ winning condition
@@ -70,61 +195,89 @@ TODO:
   ✅ make sure first guess is not a bug
 
   Nice to have:
-  Allow multi-open
-  store sequence of moves
+  ✅ Allow multi-open
+  ✅ store sequence of moves
   award NFT on game win
 */
 impl Game {
     fn set_field(&mut self, x: u8, y: u8, value: u8) {
-        let field = (x + y * WIDTH) as usize;
-        let field_bit_offset = field * 4;
-
-        let mut current256: [u8; 32] = self.board_encoded.get().to_le_bytes();
-        let byte_index = field_bit_offset / 8;
-        let bit_shift = field_bit_offset % 8;
-        let mask = 0xFu8 << bit_shift;
-        current256[byte_index] = (current256[byte_index] & !mask) | (value << bit_shift);
-        self.board_encoded.set(U256::from_le_bytes(current256));
+        let field = x as usize + y as usize * self.width() as usize;
+        packed::set_field(&mut self.board_encoded, field, value);
     }
 
     fn get_field(&self) -> GameData {
-        let current256: [u8; 32] = self.board_encoded.get().to_le_bytes();
-        let mut fields = Vec::new();
-        for index in 0..WIDTH * HEIGHT {
-            let mut field_byte = current256[(index / 2) as usize];
-            if index % 2 == 1 {
-                field_byte >>= 4;
-            } else {
-                field_byte &= 0xF;
-            }
-            fields.push(field_byte);
+        let (width, height) = (self.width(), self.height());
+        let fields = packed::fields(&self.board_encoded, width as usize * height as usize);
+        GameData::new(width, height, fields)
+    }
+
+    fn width(&self) -> u8 {
+        self.width.get().byte(0)
+    }
+
+    fn height(&self) -> u8 {
+        self.height.get().byte(0)
+    }
+
+    // Bugs as chosen with the difficulty of the game
+    fn bugs(&self) -> Bugs {
+        let amount = self.bugs.get().to::<u16>();
+        match self.difficulty.get().byte(0) {
+            DIFFICULTY_TOTAL => Bugs::Total(amount),
+            _ => Bugs::Percentage(amount as u8),
         }
-        GameData::new(WIDTH, HEIGHT, fields)
     }
 
-    pub fn init(&mut self) {
-        self.state.set(Uint::from(STATE_PLAYING));
-        for i in 0..WIDTH {
-            for j in 0..HEIGHT {
-                self.set_field(i, j, UNOPENED);
-            }
+    pub fn init(&mut self, mode: GameMode, width: u8, height: u8, difficulty: Difficulty, bugs: u16) -> Result<(), GameError> {
+        if mode != MODE_NORMAL && mode != MODE_EVIL && mode != MODE_FORGIVING {
+            return Err(GameError::UnknownMode(UnknownMode { mode }));
+        }
+        if !(MIN_SIZE..=MAX_WIDTH).contains(&width) || !(MIN_SIZE..=MAX_HEIGHT).contains(&height) {
+            return Err(GameError::InvalidBoardSize(InvalidBoardSize { width, height }));
         }
+        // The first guess is never a bug, so at least one field has to be bug-free
+        let bugs = match difficulty {
+            DIFFICULTY_BEGINNER => BUG_CHANCE_BEGINNER,
+            DIFFICULTY_INTERMEDIATE => BUG_CHANCE_INTERMEDIATE,
+            DIFFICULTY_EXPERT => BUG_CHANCE_EXPERT,
+            DIFFICULTY_PERCENTAGE if bugs <= MAX_BUG_CHANCE => bugs,
+            DIFFICULTY_TOTAL if (bugs as usize) < width as usize * height as usize => bugs,
+            _ => return Err(GameError::InvalidDifficulty(InvalidDifficulty { difficulty, bugs })),
+        };
+        self.mode.set(Uint::from(mode));
+        self.state.set(Uint::from(STATE_PLAYING));
+        self.width.set(Uint::from(width));
+        self.height.set(Uint::from(height));
+        self.difficulty.set(Uint::from(difficulty));
+        self.bugs.set(Uint::from(bugs));
+
+        // The words of a previous bigger game are dropped
+        packed::reset(&mut self.board_encoded, width as usize * height as usize);
+        self.moves.truncate(0);
+
+        evm::log(GameStarted {
+            player: msg::sender(),
+            mode,
+            width,
+            height,
+            difficulty,
+            bugs,
+        });
+        Ok(())
     }
 
     fn print_field(field_data: GameData, state: u8) -> String {
         let mut res = String::new();
-        for j in 0..HEIGHT {
-            for i in 0..WIDTH {
-                let fieldval = field_data.get(i, j).data;
-                if fieldval == BUG {
-                    res.push_str("X");
-                } else if fieldval == UNOPENED || fieldval == UNOPENED_BUGFREE{
-                    res.push_str(" ");
+        for row in field_data.data().chunks(field_data.width() as usize) {
+            for &fieldval in row {
+                // Don't show players which unopened fields are known to be bug-free
+                if fieldval == UNOPENED || fieldval == UNOPENED_BUGFREE {
+                    res.push(' ');
                 } else {
-                    res.push_str(&fieldval.to_string());
+                    res.push(to_char(fieldval));
                 }
             }
-            res.push_str("\n");
+            res.push('\n');
         }
         match state {
             STATE_PLAYING => res.push_str("Playing\n"),
@@ -143,9 +296,9 @@ impl Game {
         Self::print_field(field_data, self.state.get().byte(0))
     }
 
-    pub fn print_filled_in(&self, rand_seed: u64) -> String {
-        let filled_in =  self.get_field().fill_in(rand_seed, BUG_CHANCE_100);
-        Self::print_field(filled_in, self.state.get().byte(0))
+    pub fn print_filled_in(&self, rand_seed: u64) -> Result<String, GameError> {
+        let filled_in = self.get_field().fill_in(rand_seed, self.bugs())?;
+        Ok(Self::print_field(filled_in, self.state.get().byte(0)))
     }
 
     pub fn make_guess(&mut self, x: u8, y: u8, rand_seed: u64) -> Result<u8, GameError> {
@@ -153,92 +306,151 @@ impl Game {
             return Err(GameError::GameAlreadyOver(GameAlreadyOver {}));
         }
 
-        let mut field_data = self.get_field();
-        let field = field_data.get(x, y).data;
-        if is_open(field) {
-            return Err(GameError::FieldAlreadyOpened(FieldAlreadyOpened {}));
-        }
-        // If this is the very first guess, make sure it's not a bug
-        if field_data.num_open == 0 {
-            field_data.set_data(x, y, UNOPENED_BUGFREE);
-        }
-        // fill in the field with a possible solution
-        let mut filled_in = field_data.fill_in(rand_seed, BUG_CHANCE_100);
-        console!("filled in: \n{}", filled_in.to_string());
-
-        if filled_in.get(x, y).data == BUG {
-            evm::log(FieldOpened {
-                player: msg::sender(),
-                x,
-                y,
-                value: BUG,
-            });
-            self.set_field(x, y, BUG);
-            evm::log(GameOver {
-                player: msg::sender(),
-                won: false,
-            });
-            self.state.set(Uint::from(STATE_LOST));
-            return Ok(BUG);
-        }
-
-        let count = self.do_open(x, y, &mut filled_in);
-        // Check if won
-        let mut won = true;
-        for i in 0..WIDTH {
-            for j in 0..HEIGHT {
-                let field = filled_in.get(i, j).data;
-                if field == UNOPENED_BUGFREE {
-                    won = false;
-                    break;
+        let events = rules::make_guess(&self.get_field(), x, y, rand_seed, self.bugs(), self.mode())?;
+        // The guessed field is always opened first
+        let mut result = None;
+        for event in events {
+            match event {
+                Event::FieldOpened { x, y, value, bug_chance } => {
+                    result.get_or_insert(value);
+                    self.set_field(x, y, value);
+                    evm::log(FieldOpened {
+                        player: msg::sender(),
+                        x,
+                        y,
+                        value,
+                        bug_chance,
+                    });
+                }
+                Event::GameOver { won } => {
+                    evm::log(GameOver {
+                        player: msg::sender(),
+                        won,
+                    });
+                    self.state.set(Uint::from(if won { STATE_WON } else { STATE_LOST }));
                 }
             }
         }
-        if won {
-            evm::log(GameOver {
-                player: msg::sender(),
-                won: true,
-            });
-            self.state.set(Uint::from(STATE_WON));
-        }
+
+        let value = result.unwrap_or(BUG);
+        let mut guess = self.moves.grow();
+        guess.x.set(Uint::from(x));
+        guess.y.set(Uint::from(y));
+        guess.value.set(Uint::from(value));
+        guess.rand_seed.set(Uint::from(rand_seed));
 
         console!("current field: {}", self.print());
-        Ok(count)
+        // Safety net while debugging: whatever we store has to remain a valid board
+        #[cfg(feature = "debug")]
+        self.get_field().validate()?;
+        Ok(value)
     }
 
-    fn do_open(&mut self, x: u8, y: u8, field_data: &mut GameData) -> u8 {
-        let count = field_data.get(x, y).adjacent_bugs;
-        evm::log(FieldOpened {
+    // Give up the game that is being played, it counts as lost. When the board got too complex to solve
+    // and guesses revert with BoardTooComplex, this lets the player start a new game.
+    pub fn abandon(&mut self) -> Result<(), GameError> {
+        if self.state.get().byte(0) != STATE_PLAYING {
+            return Err(GameError::GameAlreadyOver(GameAlreadyOver {}));
+        }
+        evm::log(GameOver {
+            player: msg::sender(),
+            won: false,
+        });
+        self.state.set(Uint::from(STATE_LOST));
+        Ok(())
+    }
+
+    // Flag an unopened field as a suspected bug, or remove the flag. Returns whether the field is flagged now.
+    pub fn toggle_flag(&mut self, x: u8, y: u8) -> Result<bool, GameError> {
+        if self.state.get().byte(0) != STATE_PLAYING {
+            return Err(GameError::GameAlreadyOver(GameAlreadyOver {}));
+        }
+        if x >= self.width() || y >= self.height() {
+            return Err(GameError::FieldOutsideBoard(FieldOutsideBoard {}));
+        }
+        let field = self.get_field();
+        if is_open(field.get(x, y).data) {
+            return Err(GameError::FieldAlreadyOpened(FieldAlreadyOpened {}));
+        }
+        let flagged = !field.is_flagged(x, y);
+        self.set_field(x, y, if flagged { FLAGGED } else { UNOPENED });
+        evm::log(FieldFlagged {
             player: msg::sender(),
             x,
             y,
-            value: count,
+            flagged,
         });
-        self.set_field(x, y, count);
-        field_data.set_data(x, y, count);
+        Ok(flagged)
+    }
 
-        if count == 0 {
-            self.open_adjacent(x, y, field_data);
+    // Make the guesses (xs[i], ys[i]) in order, until the game is over. Returns the value of every field
+    // that was guessed. A field that an earlier guess of the same batch opened, e.g. around a 0, just
+    // reports its value. Any other guess that make_guess refuses, like a field outside the board, a
+    // flagged field or a field that was opened before the batch, reverts all guesses.
+    pub fn make_guesses(&mut self, xs: &[u8], ys: &[u8], rand_seed: u64) -> Result<Vec<u8>, GameError> {
+        if xs.len() != ys.len() {
+            return Err(GameError::MismatchedGuesses(MismatchedGuesses {
+                xs: U256::from(xs.len()),
+                ys: U256::from(ys.len()),
+            }));
+        }
+        let before = self.get_field();
+        let mut results = Vec::new();
+        for (index, (&x, &y)) in xs.iter().zip(ys).enumerate() {
+            if self.state.get().byte(0) != STATE_PLAYING && index > 0 {
+                break;
+            }
+            let value = match self.make_guess(x, y, rand_seed.wrapping_add(index as u64)) {
+                Err(GameError::FieldAlreadyOpened(_)) if !is_open(before.get(x, y).data) => self.get_field().get(x, y).data,
+                result => result?,
+            };
+            results.push(value);
         }
-        count
+        Ok(results)
     }
 
-    fn open_adjacent(&mut self, x: u8, y: u8, field_data: &mut GameData) {
-        for i in -1..=1 {
-            for j in -1..=1 {
-                if i == 0 && j == 0 {
-                    continue;
-                }
-                let x = x as i8 + i;
-                let y = y as i8 + j;
-                if x < 0 || x as u8 >= WIDTH || y < 0 || y as u8 >= HEIGHT {
-                    continue;
-                }
-                if !is_open(field_data.get(x as u8, y as u8).data) {
-                    self.do_open(x as u8, y as u8, field_data);
-                }
-            }
+    // The bugs that are neither opened nor flagged, only known for games with DIFFICULTY_TOTAL
+    pub fn remaining_bugs(&self) -> Result<u16, GameError> {
+        match self.bugs() {
+            Bugs::Total(total) if self.is_started() => Ok(self.get_field().remaining_bugs(total)),
+            _ => Err(GameError::NoBugTotal(NoBugTotal {})),
+        }
+    }
+
+    // The guesses made so far, oldest first
+    pub fn moves(&self) -> MoveHistory {
+        let mut history: MoveHistory = Default::default();
+        for index in 0..self.moves.len() {
+            let guess = self.moves.getter(index).unwrap();
+            history.0.push(guess.x.get().byte(0));
+            history.1.push(guess.y.get().byte(0));
+            history.2.push(guess.value.get().byte(0));
+            history.3.push(guess.rand_seed.get().to::<u64>());
+        }
+        history
+    }
+
+    pub fn move_count(&self) -> u32 {
+        self.moves.len() as u32
+    }
+
+    fn mode(&self) -> Mode {
+        match self.mode.get().byte(0) {
+            MODE_EVIL => Mode::Evil,
+            MODE_FORGIVING => Mode::Forgiving,
+            _ => Mode::Normal,
+        }
+    }
+
+    // The field to open next and its chance of being a bug in parts per million, see hint::suggest_move
+    pub fn suggest_move(&self, lookahead: bool) -> Result<(u8, u8, u32), GameError> {
+        if self.state.get().byte(0) != STATE_PLAYING {
+            return Err(GameError::GameAlreadyOver(GameAlreadyOver {}));
         }
+        let hints = suggest_move(&self.get_field(), self.bugs(), lookahead)?;
+        // A game that is still being played has unopened fields left
+        let best = &hints[0];
+        Ok((best.x, best.y, best.bug_chance.per_million()))
     }
 
     pub fn is_started(&self) -> bool {
@@ -6,9 +6,10 @@ import { chainsweepAbi } from '../src/generated';
 import { contractAddress, targetChain } from './constants';
 
 // Copying the events here because `cargo stylus export-abi` doesn't export the event data   
-const eventAbi = parseAbi(['event GameStarted(address indexed player)',
-    `event FieldOpened(address indexed player, uint8 x, uint8 y, uint8 value)`,
-    `event GameOver(address indexed player, bool won)`]);
+const eventAbi = parseAbi(['event GameStarted(address indexed player, uint8 mode, uint8 width, uint8 height, uint8 difficulty, uint16 bugs)',
+    `event FieldOpened(address indexed player, uint8 x, uint8 y, uint8 value, uint32 bug_chance)`,
+    `event GameOver(address indexed player, bool won)`,
+    `event FieldFlagged(address indexed player, uint8 x, uint8 y, bool flagged)`]);
 
 export enum GameState {
     UNSTARTED,